@@ -1,210 +1,716 @@
-use crate::{cpu::BusModule, utils::array};
-
-static CARTRIDGE_TYPE: [&'static str; 256] = array!["Unknown"; 256;
-  [0x00] = "ROM ONLY",
-  [0x01] = "MBC1",
-  [0x02] = "MBC1+RAM",
-  [0x03] = "MBC1+RAM+BATTERY",
-  [0x05] = "MBC2",
-  [0x06] = "MBC2+BATTERY",
-  [0x08] = "ROM+RAM 9",
-  [0x09] = "ROM+RAM+BATTERY 9",
-  [0x0B] = "MMM01",
-  [0x0C] = "MMM01+RAM",
-  [0x0D] = "MMM01+RAM+BATTERY",
-  [0x0F] = "MBC3+TIMER+BATTERY",
-  [0x10] = "MBC3+TIMER+RAM+BATTERY 10",
-  [0x11] = "MBC3",
-  [0x12] = "MBC3+RAM 10",
-  [0x13] = "MBC3+RAM+BATTERY 10",
-  [0x19] = "MBC5",
-  [0x1A] = "MBC5+RAM",
-  [0x1B] = "MBC5+RAM+BATTERY",
-  [0x1C] = "MBC5+RUMBLE",
-  [0x1D] = "MBC5+RUMBLE+RAM",
-  [0x1E] = "MBC5+RUMBLE+RAM+BATTERY",
-  [0x20] = "MBC6",
-  [0x22] = "MBC7+SENSOR+RUMBLE+RAM+BATTERY",
-  [0xFC] = "POCKET CAMERA",
-  [0xFD] = "BANDAI TAMA5",
-  [0xFE] = "HuC3",
-  [0xFF] = "HuC1+RAM+BATTERY",
-];
-
-fn cartridge_type_name(header: &RomHeader) -> &'static str {
-    match header.cart_type {
-        cart_type if (cart_type as usize) < CARTRIDGE_TYPE.len() => {
-            CARTRIDGE_TYPE[cart_type as usize]
-        }
-        _ => "Unknown",
-    }
-}
-
-macro_rules! new_licensee_code_array {
-    ($def:expr; $len:expr; $([$idx1:expr,$idx2:expr]=$val:expr),* $(,)?) => { {
-        let mut a = [$def; $len];
-        $(
-            a[($idx2 as usize) << 8 | ($idx1 as usize)] = $val;
-        )*
-        a
-    } }
-}
+mod archive;
+mod bootleg;
+mod camera;
+mod clock;
+mod dat;
+mod gbx;
+pub(crate) mod header;
+mod huc1;
+mod huc3;
+mod infrared;
+mod mapper;
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+mod mbc7;
+mod mmm01;
+mod patch;
+mod rom_only;
+mod sachen;
+mod tama5;
+mod wisdom_tree;
 
-static NEW_LICENSEE_CODE: [&'static str; 65535] = new_licensee_code_array!["Unknown"; 65535;
-  ['0', '0'] =	"None",
-  ['0', '1'] =	"Nintendo Research & Development 1",
-  ['0', '8'] =	"Capcom",
-  ['1', '3'] =	"EA (Electronic Arts)",
-  ['1', '8'] =	"Hudson Soft",
-  ['1', '9'] =	"B-AI",
-  ['2', '0'] =	"KSS",
-  ['2', '2'] =	"Planning Office WADA",
-  ['2', '4'] =	"PCM Complete",
-  ['2', '5'] =	"San-X",
-  ['2', '8'] =	"Kemco",
-  ['2', '9'] =	"SETA Corporation",
-  ['3', '0'] =	"Viacom",
-  ['3', '1'] =	"Nintendo",
-  ['3', '2'] =	"Bandai",
-  ['3', '3'] =	"Ocean Software/Acclaim Entertainment",
-  ['3', '4'] =	"Konami",
-  ['3', '5'] =	"HectorSoft",
-  ['3', '7'] =	"Taito",
-  ['3', '8'] =	"Hudson Soft",
-  ['3', '9'] =	"Banpresto",
-  ['4', '1'] =	"Ubi Soft1",
-  ['4', '2'] =	"Atlus",
-  ['4', '4'] =	"Malibu Interactive",
-  ['4', '6'] =	"Angel",
-  ['4', '7'] =	"Bullet-Proof Software2",
-  ['4', '9'] =	"Irem",
-  ['5', '0'] =	"Absolute",
-  ['5', '1'] =	"Acclaim Entertainment",
-  ['5', '2'] =	"Activision",
-  ['5', '3'] =	"Sammy USA Corporation",
-  ['5', '4'] =	"Konami",
-  ['5', '5'] =	"Hi Tech Expressions",
-  ['5', '6'] =	"LJN",
-  ['5', '7'] =	"Matchbox",
-  ['5', '8'] =	"Mattel",
-  ['5', '9'] =	"Milton Bradley Company",
-  ['6', '0'] =	"Titus Interactive",
-  ['6', '1'] =	"Virgin Games Ltd.3",
-  ['6', '4'] =	"Lucasfilm Games4",
-  ['6', '7'] =	"Ocean Software",
-  ['6', '9'] =	"EA (Electronic Arts)",
-  ['7', '0'] =	"Infogrames5",
-  ['7', '1'] =	"Interplay Entertainment",
-  ['7', '2'] =	"Broderbund",
-  ['7', '3'] =	"Sculptured Software6",
-  ['7', '5'] =	"The Sales Curve Limited7",
-  ['7', '8'] =	"THQ",
-  ['7', '9'] =	"Accolade",
-  ['8', '0'] =	"Misawa Entertainment",
-  ['8', '3'] =	"lozc",
-  ['8', '6'] =	"Tokuma Shoten",
-  ['8', '7'] =	"Tsukuda Original",
-  ['9', '1'] =	"Chunsoft Co.8",
-  ['9', '2'] =	"Video System",
-  ['9', '3'] =	"Ocean Software/Acclaim Entertainment",
-  ['9', '5'] =	"Varie",
-  ['9', '6'] =	"Yonezawa/s’pal",
-  ['9', '7'] =	"Kaneko",
-  ['9', '9'] =	"Pack-In-Video",
-  ['A', '4'] =	"Konami (Yu-Gi-Oh!)",
-];
-
-fn cartridge_licensee_name(header: &RomHeader) -> &'static str {
-    match header.new_licensee_code {
-        [code1, code2] if ((code2 as usize) << 8 | code1 as usize) < NEW_LICENSEE_CODE.len() => {
-            NEW_LICENSEE_CODE[(code2 as usize) << 8 | code1 as usize]
-        }
-        _ => "Unknown",
-    }
-}
-
-#[repr(C)]
-pub struct RomHeader {
-    entry: [u8; 4],
-    logo: [u8; 0x30],
-    title: [u8; 16],
-    new_licensee_code: [u8; 2],
-    sgb_flag: u8,
-    cart_type: u8,
-    rom_size: u8,
-    ram_size: u8,
-    dest_code: u8,
-    old_licensee_code: u8,
-    version: u8,
-    checksum: u8,
-    global_checksum: u16,
-}
+use crate::cpu::BusModule;
+use crate::utils::crc32;
+pub use archive::{entry_names, extract};
+use bootleg::Mbc1Bootleg;
+use camera::{Camera, ImageSource};
+pub use clock::SystemClock;
+pub use dat::{Dat, DatEntry, DumpStatus};
+use gbx::GbxFooter;
+use header::NINTENDO_LOGO;
+pub use header::RomHeader;
+use huc1::HuC1;
+use huc3::HuC3;
+pub use infrared::Infrared;
+pub use mapper::{Capabilities, Mapper, MapperConfig, MapperKind};
+use mbc1::Mbc1;
+use mbc2::Mbc2;
+use mbc3::Mbc3;
+use mbc5::Mbc5;
+use mbc7::Mbc7;
+use mmm01::Mmm01;
+pub use patch::PatchError;
+use rom_only::RomOnly;
+use sachen::Sachen;
+use tama5::Tama5;
+use wisdom_tree::WisdomTree;
 
-impl RomHeader {
-    pub fn title_str(&self) -> String {
-        String::from_utf8_lossy(&self.title).to_string()
-    }
+#[derive(Debug, Clone, PartialEq)]
+pub enum CartridgeError {
+    /* 文件不足以包含 0x0100-0x014F 的 header */
+    TooSmall(usize),
+    /* header 中的卡带类型没有对应的 mapper 实现 */
+    UnsupportedType(u8),
+    /* GBX footer 或手动指定的 mapper 没有实现 */
+    UnsupportedMapper(String),
+    /* soft patch 无法应用到 ROM 上 */
+    Patch(PatchError),
 }
 
-impl std::fmt::Debug for RomHeader {
+impl std::fmt::Display for CartridgeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("RomHeader")
-            .field("title_str", &self.title_str())
-            .field("type", &format!("{:x?}", &self.cart_type))
-            .field("new_licensee_code", &self.new_licensee_code)
-            .field("licensee_name", &cartridge_licensee_name(&self))
-            .field("rom_size", &self.rom_size)
-            .field("ram_size", &self.ram_size)
-            .field("old_licensee_code", &self.old_licensee_code)
-            .field("checksum", &self.checksum)
-            .finish()
+        match self {
+            CartridgeError::TooSmall(len) => {
+                write!(f, "ROM is too small to contain a header ({} bytes)", len)
+            }
+            CartridgeError::UnsupportedType(cart_type) => write!(
+                f,
+                "unsupported cartridge type 0x{:02X} ({})",
+                cart_type,
+                header::CARTRIDGE_TYPE[*cart_type as usize]
+            ),
+            CartridgeError::UnsupportedMapper(name) => write!(f, "unsupported mapper {}", name),
+            CartridgeError::Patch(err) => write!(f, "failed to apply patch: {}", err),
+        }
     }
 }
 
+impl std::error::Error for CartridgeError {}
+
 /**
  * 卡带
  */
 pub struct Cartridge {
     pub data: Vec<u8>,
+    header: RomHeader,
+    mapper: Box<dyn Mapper>,
+    rumbling: bool,
+    rumble_callback: Option<Box<dyn FnMut(bool)>>,
+    /* 上次导出存档之后外部 RAM 是否被写入过 */
+    save_dirty: bool,
+    /* 补齐之前的 ROM 数据的 hash，用于在 DAT 中识别 ROM */
+    size: usize,
+    crc32: u32,
+    sha1: [u8; 20],
+    dat_entry: Option<DatEntry>,
 }
 
 impl std::fmt::Debug for Cartridge {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Cartridge")
-            .field("header", &self.as_header())
+            .field("header", &self.header)
+            .field("capabilities", &self.capabilities())
             .field("is_checksum_matched", &self.is_checksum_match())
             .finish()
     }
 }
 
 impl Cartridge {
-    pub fn from(data: Vec<u8>) -> Self {
-        Cartridge { data }
+    pub fn from(data: Vec<u8>) -> Result<Self, CartridgeError> {
+        Self::load(data, None, None)
+    }
+
+    /**
+     * 先在内存中应用 IPS / UPS / BPS patch 再加载，原始文件不会被修改。
+     * mapper 用于在自动识别出错时手动指定，优先于 GBX footer 与 header
+     */
+    pub fn load(
+        mut data: Vec<u8>,
+        patch: Option<&[u8]>,
+        mapper: Option<MapperKind>,
+    ) -> Result<Self, CartridgeError> {
+        if let Some(patch) = patch {
+            data = patch::apply(&data, patch).map_err(CartridgeError::Patch)?;
+        }
+        /* GBX footer 不属于 ROM 数据，hash 与 bank 计算都不包含它 */
+        let gbx = GbxFooter::parse(&data);
+        if let Some(gbx) = &gbx {
+            data.truncate(data.len() - gbx.size);
+        }
+        let global_checksum = RomHeader::global_checksum(&data);
+        let header = RomHeader::parse(&data)?;
+        let size = data.len();
+        let crc32 = crc32(&data);
+        let sha1 = sha1_smol::Sha1::from(&data).digest().bytes();
+        /* 不足 32KB 的 ROM 补齐，未接线的地址读出 0xFF */
+        let padded_size = gbx.as_ref().map_or(0, |gbx| gbx.rom_size).max(0x8000);
+        if data.len() < padded_size {
+            data.resize(padded_size, 0xFF);
+        }
+        let header = Self::mmm01_header(&data, global_checksum).unwrap_or(header);
+        let mut cartridge = Cartridge {
+            data,
+            header,
+            mapper: Box::new(RomOnly::new(0, false)),
+            rumbling: false,
+            rumble_callback: None,
+            save_dirty: false,
+            size,
+            crc32,
+            sha1,
+            dat_entry: None,
+        };
+        let config = match (mapper, &gbx) {
+            (Some(kind), Some(gbx)) => gbx.config_with_kind(kind),
+            (Some(kind), None) => cartridge.header_config(Some(kind))?,
+            (None, Some(gbx)) => gbx.config()?,
+            (None, None) => cartridge.header_config(None)?,
+        };
+        /* Sachen 卡带的 header 以打乱的顺序存储 */
+        if let MapperKind::SachenMmc1 | MapperKind::SachenMmc2 = config.kind {
+            cartridge.header = RomHeader::parse_with_global_checksum(
+                &sachen::unscrambled_header(&cartridge.data),
+                global_checksum,
+            )?;
+        }
+        cartridge.mapper = Self::create_mapper(config);
+        Ok(cartridge)
     }
 
-    pub fn as_header<'s>(&'s self) -> &'s RomHeader {
-        unsafe { &*((self.data.as_ptr().offset(0x100)) as *const RomHeader) }
+    /**
+     * 根据 header 中的卡带类型决定 mapper，无授权卡带的 header 不可信，需要按 ROM 内容识别。
+     * kind 不为空时只从 header 中读取 RAM 大小等信息
+     */
+    fn header_config(&self, kind: Option<MapperKind>) -> Result<MapperConfig, CartridgeError> {
+        let cart_type = self.header.cart_type();
+        let rom_size = self.header.rom_size_bytes();
+        let kind = match (kind, cart_type) {
+            (Some(kind), _) => kind,
+            _ if Sachen::detect(&self.data) => {
+                match sachen::unscrambled_header(&self.data)[0x143] {
+                    0x80 | 0xC0 => MapperKind::SachenMmc2,
+                    _ => MapperKind::SachenMmc1,
+                }
+            }
+            _ if WisdomTree::detect(cart_type, &self.data) => MapperKind::WisdomTree,
+            (_, 0x00 | 0x08 | 0x09) => MapperKind::RomOnly,
+            (_, 0x01..=0x03) if self.is_mbc1_multicart() => MapperKind::Mbc1Multicart,
+            (_, 0x01..=0x03) if Mbc1Bootleg::detect(&self.data, rom_size) => {
+                MapperKind::Mbc1Bootleg
+            }
+            (_, 0x01..=0x03) => MapperKind::Mbc1,
+            (_, 0x05 | 0x06) => MapperKind::Mbc2,
+            (_, 0x0B..=0x0D) => MapperKind::Mmm01,
+            (_, 0x0F..=0x13) => MapperKind::Mbc3,
+            (_, 0x19..=0x1E) => MapperKind::Mbc5,
+            (_, 0x22) => MapperKind::Mbc7,
+            (_, 0xFC) => MapperKind::Camera,
+            (_, 0xFD) => MapperKind::Tama5,
+            (_, 0xFE) => MapperKind::HuC3,
+            (_, 0xFF) => MapperKind::HuC1,
+            _ => return Err(CartridgeError::UnsupportedType(cart_type)),
+        };
+        Ok(MapperConfig {
+            kind,
+            ram_size: self.header.ram_size_bytes(),
+            battery: matches!(
+                cart_type,
+                0x03 | 0x06 | 0x09 | 0x0D | 0x0F | 0x10 | 0x13 | 0x1B | 0x1E | 0x22
+            ),
+            rtc: matches!(cart_type, 0x0F | 0x10),
+            rumble: matches!(cart_type, 0x1C..=0x1E),
+        })
     }
 
-    pub fn is_checksum_match(&self) -> bool {
-        let mut x: u16 = 0;
-        for i in 0x0134..=0x014C {
-            x = u16::wrapping_sub(x, (self.data[i] as u16) + 1);
+    fn create_mapper(config: MapperConfig) -> Box<dyn Mapper> {
+        let MapperConfig {
+            kind,
+            ram_size,
+            battery,
+            rtc,
+            rumble,
+        } = config;
+        match kind {
+            MapperKind::RomOnly => Box::new(RomOnly::new(ram_size, battery)),
+            MapperKind::Mbc1 => Box::new(Mbc1::new(ram_size, false, battery)),
+            MapperKind::Mbc1Multicart => Box::new(Mbc1::new(ram_size, true, battery)),
+            MapperKind::Mbc2 => Box::new(Mbc2::new(battery)),
+            MapperKind::Mbc3 => Box::new(Mbc3::new(
+                ram_size,
+                match rtc {
+                    true => Some(Box::new(SystemClock)),
+                    false => None,
+                },
+                battery,
+            )),
+            MapperKind::Mbc5 => Box::new(Mbc5::new(ram_size, rumble, battery)),
+            MapperKind::Mbc7 => Box::new(Mbc7::new(ram_size, battery)),
+            MapperKind::Mmm01 => Box::new(Mmm01::new(ram_size, battery)),
+            MapperKind::Camera => Box::new(Camera::new(ram_size)),
+            MapperKind::Tama5 => Box::new(Tama5::new(Box::new(SystemClock))),
+            MapperKind::HuC1 => Box::new(HuC1::new(ram_size)),
+            MapperKind::HuC3 => Box::new(HuC3::new(ram_size, Box::new(SystemClock))),
+            MapperKind::WisdomTree => Box::new(WisdomTree::new()),
+            MapperKind::SachenMmc1 => Box::new(Sachen::new(false)),
+            MapperKind::SachenMmc2 => Box::new(Sachen::new(true)),
+            MapperKind::Mbc1Bootleg => Box::new(Mbc1Bootleg::new(ram_size, battery)),
+        }
+    }
+
+    pub fn header(&self) -> &RomHeader {
+        &self.header
+    }
+
+    /**
+     * MMM01 的菜单位于 ROM 最后 32KB，卡带类型只记录在菜单的 header 中，
+     * 0x0100 处是第一个子游戏的 header
+     */
+    fn mmm01_header(data: &[u8], global_checksum: u16) -> Option<RomHeader> {
+        let base = data.len().checked_sub(0x8000)?;
+        let header = RomHeader::parse_with_global_checksum(&data[base..], global_checksum).ok()?;
+        match header.cart_type() {
+            0x0B..=0x0D => Some(header),
+            _ => None,
+        }
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        self.mapper.capabilities()
+    }
+
+    pub fn has_battery(&self) -> bool {
+        self.capabilities().battery
+    }
+
+    pub fn is_rumbling(&self) -> bool {
+        self.mapper.is_rumbling()
+    }
+
+    /**
+     * 马达状态变化时回调，参数为是否正在振动
+     */
+    pub fn set_rumble_callback(&mut self, callback: Box<dyn FnMut(bool)>) {
+        self.rumble_callback = Some(callback);
+    }
+
+    /**
+     * 设置 MBC7 倾斜传感器的数据来源，返回 X/Y 方向的加速度，单位为 g
+     */
+    pub fn set_tilt_source(&mut self, tilt: Box<dyn Fn() -> (f32, f32)>) {
+        self.mapper.set_tilt_source(tilt);
+    }
+
+    /**
+     * 替换 RTC 使用的宿主时钟
+     */
+    #[cfg(test)]
+    pub fn set_clock(&mut self, clock: Box<dyn clock::Clock>) {
+        self.mapper.set_clock(clock);
+    }
+
+    /**
+     * 连接 HuC1/HuC3 的红外收发口
+     */
+    pub fn set_infrared(&mut self, infrared: Box<dyn Infrared>) {
+        self.mapper.set_infrared(infrared);
+    }
+
+    /**
+     * HuC3 蜂鸣器状态变化时回调，Some(tone) 表示开始发声
+     */
+    pub fn set_tone_callback(&mut self, callback: Box<dyn FnMut(Option<u8>)>) {
+        self.mapper.set_tone_callback(callback);
+    }
+
+    /**
+     * 设置 Pocket Camera 拍摄的图像，data 为 width x height 的灰度或 RGBA 像素
+     */
+    pub fn set_camera_image(&mut self, data: &[u8], width: usize, height: usize) -> bool {
+        match camera::sensor_image_from(data, width, height) {
+            Some(image) => self.mapper.set_camera_image(image),
+            None => false,
+        }
+    }
+
+    /**
+     * 每次开始拍摄时调用 source 获取最新画面（灰度或 RGBA 像素、宽、高）
+     */
+    pub fn set_camera_source(&mut self, source: ImageSource) {
+        self.mapper.set_camera_source(source);
+    }
+
+    /**
+     * 最近一次拍摄的 128x112 图像，每个像素为 0-3 的颜色编号
+     */
+    pub fn camera_capture(&self) -> Option<Vec<u8>> {
+        self.mapper.camera_capture()
+    }
+
+    /**
+     * 卡带上需要随 CPU 运行的部件，每个 M-cycle 调用一次
+     */
+    pub fn tick(&mut self) {
+        self.mapper.tick();
+    }
+
+    pub fn skip_boot(&mut self) {
+        self.mapper.skip_boot();
+    }
+
+    /**
+     * 导出电池供电的外部 RAM，没有电池的卡带返回 None
+     * 带 RTC 的卡带会在末尾附加 RTC footer
+     */
+    pub fn save_data(&self) -> Option<Vec<u8>> {
+        match self.has_battery() {
+            true => Some(self.mapper.save_data()),
+            false => None,
+        }
+    }
+
+    /**
+     * 与 save_data 相同，但会清除 dirty 标记
+     */
+    pub fn export_save_data(&mut self) -> Option<Vec<u8>> {
+        self.save_dirty = false;
+        self.save_data()
+    }
+
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        if self.has_battery() {
+            self.mapper.load_save_data(data);
+            self.save_dirty = false;
+        }
+    }
+
+    /**
+     * 只按外部 RAM 区域的写入判断，RTC 的走时不会标记为 dirty，
+     * 因为 footer 中记录了时间戳，加载时会自动补上经过的时间
+     */
+    pub fn is_save_dirty(&self) -> bool {
+        self.save_dirty
+    }
+
+    /**
+     * MBC1M 多合一卡带只把 bank2 接到 ROM 地址的第 4、5 位，
+     * 每个子游戏占 16 个 bank，且各自在 bank 0 处带有 Nintendo logo
+     */
+    fn is_mbc1_multicart(&self) -> bool {
+        if self.data.len() != 0x100000 {
+            return false;
         }
-        ((x & 0xFF) as u8) == self.as_header().checksum
+        (1..4).any(|game| {
+            let offset = game * 0x10 * 0x4000 + 0x104;
+            self.data[offset..offset + NINTENDO_LOGO.len()] == NINTENDO_LOGO
+        })
+    }
+
+    pub fn is_checksum_match(&self) -> bool {
+        self.header.is_checksum_match()
+    }
+
+    pub fn crc32(&self) -> u32 {
+        self.crc32
+    }
+
+    pub fn sha1(&self) -> [u8; 20] {
+        self.sha1
+    }
+
+    pub fn sha1_hex(&self) -> String {
+        self.sha1
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /**
+     * 在 DAT 中查找当前 ROM，找到后记录下来供 dat_entry 使用
+     */
+    pub fn identify(&mut self, dat: &Dat) -> Option<&DatEntry> {
+        self.dat_entry = dat.lookup(self.size, self.crc32, &self.sha1).cloned();
+        self.dat_entry.as_ref()
+    }
+
+    pub fn dat_entry(&self) -> Option<&DatEntry> {
+        self.dat_entry.as_ref()
     }
 }
 
 impl BusModule for Cartridge {
     fn read(&self, address: u16) -> u8 {
-        // for now just ROM ONLY
-
-        self.data[address as usize]
+        self.mapper.read(&self.data, address)
     }
 
     fn write(&mut self, address: u16, value: u8) {
-        // for now ROM ONLY
+        if self.mapper.write(address, value) {
+            self.save_dirty |= self.has_battery();
+        }
+
+        let rumbling = self.is_rumbling();
+        if rumbling != self.rumbling {
+            self.rumbling = rumbling;
+            if let Some(callback) = &mut self.rumble_callback {
+                callback(rumbling);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test]
+    fn mbc1_multicart_detection() {
+        let mut data = vec![0; 0x100000];
+        data[0x147] = 0x01;
+        data[0x104..0x134].copy_from_slice(&NINTENDO_LOGO);
+        assert!(!Cartridge::from(data.clone()).unwrap().is_mbc1_multicart());
+
+        data[0x40104..0x40134].copy_from_slice(&NINTENDO_LOGO);
+        assert!(Cartridge::from(data.clone()).unwrap().is_mbc1_multicart());
+
+        data.truncate(0x80000);
+        assert!(!Cartridge::from(data).unwrap().is_mbc1_multicart());
+    }
+
+    #[test]
+    fn short_rom_is_rejected_or_padded() {
+        assert_eq!(
+            Cartridge::from(vec![0; 0x100]).unwrap_err(),
+            CartridgeError::TooSmall(0x100)
+        );
+        let cartridge = Cartridge::from(vec![0; 0x150]).unwrap();
+        assert_eq!(cartridge.read(0x0100), 0x00);
+        assert_eq!(cartridge.read(0x7FFF), 0xFF);
+    }
+
+    #[test]
+    fn mapper_selection_and_capabilities() {
+        let mut data = vec![0; 0x8000];
+        data[0x147] = 0x09;
+        data[0x149] = 0x02;
+        let cartridge = Cartridge::from(data.clone()).unwrap();
+        assert_eq!(cartridge.save_data().map(|save| save.len()), Some(0x2000));
+
+        data[0x147] = 0x10;
+        let capabilities = Cartridge::from(data.clone()).unwrap().capabilities();
+        assert!(capabilities.battery && capabilities.rtc && !capabilities.rumble);
+
+        data[0x147] = 0x20;
+        assert_eq!(
+            Cartridge::from(data).unwrap_err().to_string(),
+            "unsupported cartridge type 0x20 (MBC6)"
+        );
+    }
+
+    #[test]
+    fn mmm01_detected_from_menu_header() {
+        let mut data: Vec<u8> = (0..0x20).flat_map(|bank| vec![bank; 0x4000]).collect();
+        data[0x147] = 0x01;
+        data[0x78147] = 0x0D;
+        data[0x78149] = 0x03;
+        /* 菜单 header 里的 global checksum 按整个 ROM 计算，再从末尾扣掉写入的两个字节 */
+        data[0x7814E..0x78150].fill(0);
+        let checksum = RomHeader::global_checksum(&data);
+        data[0x7814E..0x78150].copy_from_slice(&checksum.to_be_bytes());
+        let mut excess = (checksum >> 8) + (checksum & 0xFF);
+        for byte in data.iter_mut().rev() {
+            let delta = excess.min(*byte as u16);
+            *byte -= delta as u8;
+            excess -= delta;
+        }
+        let mut cartridge = Cartridge::from(data).unwrap();
+        assert!(cartridge.header().is_global_checksum_match());
+        assert!(cartridge.has_battery());
+        assert_eq!(cartridge.read(0x0000), 0x1E);
+        assert_eq!(cartridge.read(0x4000), 0x1F);
+
+        cartridge.write(0x0000, 0x4A);
+        assert_eq!(cartridge.read(0x0000), 0x00);
+        cartridge.write(0xA000, 0x12);
+        assert_eq!(cartridge.save_data().unwrap()[..1], [0x12]);
+    }
+
+    #[test]
+    fn save_dirty_only_on_ram_writes() {
+        let mut data = vec![0; 0x40000];
+        data[0x147] = 0x10;
+        data[0x149] = 0x02;
+        let mut cartridge = Cartridge::from(data).unwrap();
+        /* RAM 未启用 */
+        cartridge.write(0xA000, 0x12);
+        assert!(!cartridge.is_save_dirty());
+
+        /* RTC 寄存器与 latch */
+        cartridge.write(0x0000, 0x0A);
+        cartridge.write(0x4000, 0x08);
+        cartridge.write(0xA000, 0x12);
+        cartridge.write(0x6000, 0x00);
+        cartridge.write(0x6000, 0x01);
+        assert!(!cartridge.is_save_dirty());
+
+        cartridge.write(0x4000, 0x00);
+        cartridge.write(0xA000, 0x12);
+        assert!(cartridge.is_save_dirty());
+    }
+
+    #[test]
+    fn mbc2_battery_save() {
+        let mut data = vec![0; 0x40000];
+        data[0x147] = 0x06;
+        let mut cartridge = Cartridge::from(data.clone()).unwrap();
+        cartridge.write(0x0000, 0x0A);
+        cartridge.write(0xA001, 0x57);
+        assert!(cartridge.is_save_dirty());
+        let save = cartridge.export_save_data().unwrap();
+        assert_eq!(save.len(), 0x200);
+        assert_eq!(save[1], 0x07);
+        assert!(!cartridge.is_save_dirty());
+
+        let mut restored = Cartridge::from(data.clone()).unwrap();
+        restored.load_save_data(&save);
+        restored.write(0x0000, 0x0A);
+        assert_eq!(restored.read(0xA201), 0xF7);
+        assert!(!restored.is_save_dirty());
+
+        data[0x147] = 0x05;
+        assert_eq!(Cartridge::from(data).unwrap().save_data(), None);
+    }
+
+    #[test]
+    fn patched_rom() {
+        let mut data = vec![0; 0x8000];
+        data[0x147] = 0x01;
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0x00, 0x01, 0x47, 0x00, 0x01, 0x1B]);
+        patch.extend_from_slice(b"EOF");
+        let cartridge = Cartridge::load(data.clone(), Some(&patch), None).unwrap();
+        assert_eq!(cartridge.header().cart_type(), 0x1B);
+        assert_eq!(cartridge.read(0x0147), 0x1B);
+
+        assert_eq!(
+            Cartridge::load(data, Some(b"UPS1"), None).unwrap_err(),
+            CartridgeError::Patch(PatchError::Malformed("unexpected end of patch"))
+        );
+    }
+
+    #[test]
+    fn identify_with_dat() {
+        let mut data = vec![0; 0x8000];
+        data[0x134..0x138].copy_from_slice(b"TEST");
+        let mut cartridge = Cartridge::from(data.clone()).unwrap();
+        assert_eq!(cartridge.crc32(), crc32(&data));
+        let sha1 = sha1_smol::Sha1::from(&data).digest().to_string();
+        assert_eq!(
+            cartridge.sha1(),
+            sha1_smol::Sha1::from(&data).digest().bytes()
+        );
+
+        let dat = Dat::parse(&format!(
+            r#"<datafile><game name="Test (USA)"><rom name="Test (USA).gb" size="32768" crc="00000000" sha1="{}" status="baddump"/></game></datafile>"#,
+            sha1
+        ))
+        .unwrap();
+        let entry = cartridge.identify(&dat).unwrap();
+        assert_eq!(entry.name, "Test (USA)");
+        assert_eq!(entry.region.as_deref(), Some("USA"));
+        assert_eq!(entry.status, DumpStatus::BadDump);
+
+        data[0x200] = 1;
+        let mut other = Cartridge::from(data).unwrap();
+        assert_eq!(other.identify(&dat), None);
+        assert_eq!(other.dat_entry(), None);
+    }
+
+    #[test]
+    fn gbx_footer_overrides_header() {
+        /* header 写的是 ROM ONLY，footer 声明为带电池与 RTC 的 MBC3 */
+        let mut data = vec![0; 0x18000];
+        data[0x14000] = 0x42;
+        data.extend_from_slice(&gbx::tests::footer(b"MBC3", [1, 0, 1], 0x20000, 0x8000));
+        let mut cartridge = Cartridge::from(data.clone()).unwrap();
+        assert_eq!(cartridge.data.len(), 0x20000);
+        assert_eq!(cartridge.crc32(), crc32(&data[..0x18000]));
+        assert_eq!(
+            cartridge.capabilities(),
+            Capabilities {
+                battery: true,
+                rtc: true,
+                ..Default::default()
+            }
+        );
+        cartridge.write(0x2000, 0x05);
+        assert_eq!(cartridge.read(0x4000), 0x42);
+        assert_eq!(cartridge.save_data().unwrap().len(), 0x8000 + 48);
+
+        let mut data = vec![0; 0x8000];
+        data.extend_from_slice(&gbx::tests::footer(b"HITK", [1, 0, 0], 0x8000, 0x2000));
+        assert_eq!(
+            Cartridge::from(data.clone()).unwrap_err(),
+            CartridgeError::UnsupportedMapper("HITK".into())
+        );
+        /* 手动指定 mapper 时不再检查 footer 中的 mapper 名字 */
+        let cartridge = Cartridge::load(data, None, Some(MapperKind::Mbc5)).unwrap();
+        assert!(cartridge.has_battery());
+        assert_eq!(cartridge.save_data().unwrap().len(), 0x2000);
+    }
+
+    #[test]
+    fn unlicensed_detection_and_forced_mapper() {
+        let mut data: Vec<u8> = (0..4).flat_map(|bank| vec![bank; 0x8000]).collect();
+        data[0x147] = 0xC0;
+        data[0x2000..0x200B].copy_from_slice(b"WISDOM TREE");
+        let mut cartridge = Cartridge::from(data.clone()).unwrap();
+        cartridge.write(0x0002, 0x00);
+        assert_eq!(cartridge.read(0x0000), 2);
+
+        /* 手动指定时忽略识别结果 */
+        let mut cartridge = Cartridge::load(data.clone(), None, Some(MapperKind::Mbc1)).unwrap();
+        cartridge.write(0x2000, 0x03);
+        assert_eq!(cartridge.read(0x4000), 1);
+        assert_eq!(
+            "nope".parse::<MapperKind>(),
+            Err(CartridgeError::UnsupportedMapper("nope".into()))
+        );
+        assert_eq!("Wisdom-Tree".parse(), Ok(MapperKind::WisdomTree));
+
+        /* Sachen 卡带的 header 按打乱后的地址解析 */
+        for i in 0..0x30 {
+            data[sachen::unscramble(0x104 + i) as usize] = NINTENDO_LOGO[i as usize];
+        }
+        for (i, c) in b"SACHEN".iter().enumerate() {
+            data[sachen::unscramble(0x134 + i as u16) as usize] = *c;
+        }
+        let mut cartridge = Cartridge::from(data).unwrap();
+        assert_eq!(cartridge.header().title_str(), "SACHEN");
+        cartridge.skip_boot();
+        assert_eq!(cartridge.read(0x0134), b'S');
+    }
+
+    #[test]
+    fn mbc5_rumble_callback() {
+        let mut data = vec![0; 0x8000];
+        data[0x147] = 0x1C;
+        let mut cartridge = Cartridge::from(data).unwrap();
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let recorder = Rc::clone(&changes);
+        cartridge.set_rumble_callback(Box::new(move |on| recorder.borrow_mut().push(on)));
+
+        cartridge.write(0x4000, 0x08);
+        cartridge.write(0x4000, 0x0A);
+        cartridge.write(0x4000, 0x00);
+        assert_eq!(*changes.borrow(), vec![true, false]);
+    }
+
+    #[test]
+    fn replaced_clock_drives_rtc() {
+        let mut data = vec![0; 0x8000];
+        data[0x147] = 0x10;
+        data[0x149] = 0x02;
+        let mut cartridge = Cartridge::from(data).unwrap();
+        let clock = clock::tests::FakeClock::default();
+        cartridge.set_clock(Box::new(clock.clone()));
 
-        // unimplemented!();
+        cartridge.write(0x0000, 0x0A);
+        cartridge.write(0x4000, 0x08);
+        clock.advance(42);
+        cartridge.write(0x6000, 0x00);
+        cartridge.write(0x6000, 0x01);
+        assert_eq!(cartridge.read(0xA000), 42);
     }
 }
@@ -0,0 +1,356 @@
+use crate::utils::crc32;
+use miniz_oxide::inflate::decompress_to_vec_with_limit;
+
+/* 最大的 MBC5 卡带为 8MB，解压出的内容超过这个大小肯定不是 ROM */
+const MAX_ROM_SIZE: usize = 0x800000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArchiveError {
+    Malformed(&'static str),
+    /* zip 中使用了 stored / deflate 以外的压缩方式 */
+    UnsupportedCompression(u16),
+    /* zip 中没有 .gb / .gbc 文件，或没有指定名字的文件 */
+    EntryNotFound(Option<String>),
+    ChecksumMismatch { expected: u32, actual: u32 },
+}
+
+impl std::fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArchiveError::Malformed(reason) => write!(f, "malformed archive: {}", reason),
+            ArchiveError::UnsupportedCompression(method) => {
+                write!(f, "unsupported compression method {} in zip", method)
+            }
+            ArchiveError::EntryNotFound(None) => write!(f, "no .gb/.gbc file found in zip"),
+            ArchiveError::EntryNotFound(Some(name)) => write!(f, "{} not found in zip", name),
+            ArchiveError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "archive CRC32 mismatch (expected {:08X}, got {:08X})",
+                expected, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+fn is_zip(data: &[u8]) -> bool {
+    data.starts_with(b"PK\x03\x04")
+}
+
+fn is_gzip(data: &[u8]) -> bool {
+    data.starts_with(&[0x1F, 0x8B])
+}
+
+/* 偏移来自文件内容，wasm 上 usize 只有 32 位，相加时需要检查溢出 */
+fn checked_offset(base: usize, len: usize) -> Result<usize, ArchiveError> {
+    base.checked_add(len)
+        .ok_or(ArchiveError::Malformed("unexpected end of archive"))
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Result<u16, ArchiveError> {
+    data.get(offset..checked_offset(offset, 2)?)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or(ArchiveError::Malformed("unexpected end of archive"))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32, ArchiveError> {
+    data.get(offset..checked_offset(offset, 4)?)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(ArchiveError::Malformed("unexpected end of archive"))
+}
+
+fn inflate(data: &[u8]) -> Result<Vec<u8>, ArchiveError> {
+    decompress_to_vec_with_limit(data, MAX_ROM_SIZE)
+        .map_err(|_| ArchiveError::Malformed("invalid deflate stream"))
+}
+
+fn check_crc(data: Vec<u8>, expected: u32) -> Result<Vec<u8>, ArchiveError> {
+    let actual = crc32(&data);
+    match actual == expected {
+        true => Ok(data),
+        false => Err(ArchiveError::ChecksumMismatch { expected, actual }),
+    }
+}
+
+/**
+ * 根据 magic 判断是否为 zip / gzip，是则解压出其中的 ROM，否则原样返回。
+ * zip 默认取第一个 .gb / .gbc 文件，entry 不为空时取对应名字的文件
+ */
+pub fn extract(data: Vec<u8>, entry: Option<&str>) -> Result<Vec<u8>, ArchiveError> {
+    if is_zip(&data) {
+        let entries = zip_entries(&data)?;
+        let found = match entry {
+            Some(name) => entries.iter().find(|e| e.name == name),
+            None => entries.iter().find(|e| is_rom_name(&e.name)),
+        };
+        found
+            .ok_or(ArchiveError::EntryNotFound(entry.map(String::from)))?
+            .read(&data)
+    } else if is_gzip(&data) {
+        gunzip(&data)
+    } else {
+        Ok(data)
+    }
+}
+
+/**
+ * zip 中所有文件的名字，用于让用户选择要加载的文件；不是 zip 时返回空
+ */
+pub fn entry_names(data: &[u8]) -> Result<Vec<String>, ArchiveError> {
+    match is_zip(data) {
+        true => Ok(zip_entries(data)?.into_iter().map(|e| e.name).collect()),
+        false => Ok(Vec::new()),
+    }
+}
+
+fn is_rom_name(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.ends_with(".gb") || name.ends_with(".gbc")
+}
+
+/**
+ * gzip：10 字节 header，之后按 flag 跳过可选字段，末尾为 CRC32 与原始长度
+ */
+fn gunzip(data: &[u8]) -> Result<Vec<u8>, ArchiveError> {
+    if data.get(2) != Some(&8) {
+        return Err(ArchiveError::Malformed(
+            "unsupported gzip compression method",
+        ));
+    }
+    let flags = *data
+        .get(3)
+        .ok_or(ArchiveError::Malformed("unexpected end of archive"))?;
+    let mut offset = 10;
+    if flags & 0x04 != 0 {
+        offset += 2 + u16_at(data, offset)? as usize;
+    }
+    for flag in [0x08, 0x10] {
+        if flags & flag != 0 {
+            let len = data
+                .get(offset..)
+                .and_then(|rest| rest.iter().position(|&c| c == 0))
+                .ok_or(ArchiveError::Malformed("unexpected end of archive"))?;
+            offset += len + 1;
+        }
+    }
+    if flags & 0x02 != 0 {
+        offset += 2;
+    }
+    if data.len() < offset + 8 {
+        return Err(ArchiveError::Malformed("unexpected end of archive"));
+    }
+    let trailer = data.len() - 8;
+    check_crc(inflate(&data[offset..trailer])?, u32_at(data, trailer)?)
+}
+
+struct ZipEntry {
+    name: String,
+    method: u16,
+    crc: u32,
+    compressed_size: usize,
+    local_header: usize,
+}
+
+impl ZipEntry {
+    fn read(&self, data: &[u8]) -> Result<Vec<u8>, ArchiveError> {
+        let offset = self.local_header;
+        if u32_at(data, offset)? != 0x04034B50 {
+            return Err(ArchiveError::Malformed("invalid local file header"));
+        }
+        let start =
+            offset + 30 + u16_at(data, offset + 26)? as usize + u16_at(data, offset + 28)? as usize;
+        let compressed = data
+            .get(start..checked_offset(start, self.compressed_size)?)
+            .ok_or(ArchiveError::Malformed("unexpected end of archive"))?;
+        let content = match self.method {
+            0 => compressed.to_vec(),
+            8 => inflate(compressed)?,
+            method => return Err(ArchiveError::UnsupportedCompression(method)),
+        };
+        check_crc(content, self.crc)
+    }
+}
+
+/**
+ * 从文件末尾的 end of central directory 找到 central directory，
+ * 大小等信息以 central directory 为准（local header 中可能为 0）
+ */
+fn zip_entries(data: &[u8]) -> Result<Vec<ZipEntry>, ArchiveError> {
+    let end = (0..data.len().saturating_sub(21))
+        .rev()
+        .find(|&offset| data[offset..].starts_with(b"PK\x05\x06"))
+        .ok_or(ArchiveError::Malformed(
+            "end of central directory not found",
+        ))?;
+    let count = u16_at(data, end + 10)? as usize;
+    let mut offset = u32_at(data, end + 16)? as usize;
+
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        if u32_at(data, offset)? != 0x02014B50 {
+            return Err(ArchiveError::Malformed("invalid central directory"));
+        }
+        let name_len = u16_at(data, offset + 28)? as usize;
+        let extra_len = u16_at(data, offset + 30)? as usize;
+        let comment_len = u16_at(data, offset + 32)? as usize;
+        let name = data
+            .get(offset + 46..checked_offset(offset + 46, name_len)?)
+            .ok_or(ArchiveError::Malformed("unexpected end of archive"))?;
+        entries.push(ZipEntry {
+            name: String::from_utf8_lossy(name).into_owned(),
+            method: u16_at(data, offset + 10)?,
+            crc: u32_at(data, offset + 16)?,
+            compressed_size: u32_at(data, offset + 20)? as usize,
+            local_header: u32_at(data, offset + 42)? as usize,
+        });
+        offset += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miniz_oxide::deflate::compress_to_vec;
+
+    fn zip(files: &[(&str, &[u8], bool)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut directory = Vec::new();
+        for (name, content, deflate) in files {
+            let compressed = match deflate {
+                true => compress_to_vec(content, 6),
+                false => content.to_vec(),
+            };
+            let mut header = Vec::new();
+            header.extend_from_slice(&(*deflate as u16 * 8).to_le_bytes());
+            header.extend_from_slice(&[0; 4]);
+            header.extend_from_slice(&crc32(content).to_le_bytes());
+            header.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+            header.extend_from_slice(&(content.len() as u32).to_le_bytes());
+            header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            header.extend_from_slice(&[0; 2]);
+
+            directory.extend_from_slice(b"PK\x01\x02\x14\x00\x14\x00\x00\x00");
+            directory.extend_from_slice(&header);
+            directory.extend_from_slice(&[0; 10]);
+            directory.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+
+            data.extend_from_slice(b"PK\x03\x04\x14\x00\x00\x00");
+            data.extend_from_slice(&header);
+            data.extend_from_slice(name.as_bytes());
+            data.extend_from_slice(&compressed);
+        }
+        let directory_offset = data.len() as u32;
+        data.extend_from_slice(&directory);
+        data.extend_from_slice(b"PK\x05\x06\x00\x00\x00\x00");
+        data.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        data.extend_from_slice(&directory_offset.to_le_bytes());
+        data.extend_from_slice(&[0; 2]);
+        data
+    }
+
+    #[test]
+    fn extract_from_zip() {
+        let rom: Vec<u8> = (0..0x8000).map(|i| (i % 251) as u8).collect();
+        let data = zip(&[
+            ("readme.txt", b"hello", false),
+            ("Game.GB", &rom, true),
+            ("hack.gbc", &[0x11; 0x200], false),
+        ]);
+        assert_eq!(
+            entry_names(&data).unwrap(),
+            ["readme.txt", "Game.GB", "hack.gbc"]
+        );
+        assert_eq!(extract(data.clone(), None).unwrap(), rom);
+        assert_eq!(
+            extract(data.clone(), Some("hack.gbc")).unwrap(),
+            [0x11; 0x200]
+        );
+        assert_eq!(
+            extract(data.clone(), Some("missing.gb")),
+            Err(ArchiveError::EntryNotFound(Some("missing.gb".into())))
+        );
+
+        let data = zip(&[("readme.txt", b"hello", false)]);
+        assert_eq!(extract(data, None), Err(ArchiveError::EntryNotFound(None)));
+    }
+
+    #[test]
+    fn extract_from_gzip() {
+        let rom: Vec<u8> = (0..0x8000).map(|i| (i % 13) as u8).collect();
+        /* 带有 FNAME 字段的 gzip */
+        let mut data = vec![0x1F, 0x8B, 8, 0x08, 0, 0, 0, 0, 0, 3];
+        data.extend_from_slice(b"game.gb\0");
+        data.extend_from_slice(&compress_to_vec(&rom, 6));
+        data.extend_from_slice(&crc32(&rom).to_le_bytes());
+        data.extend_from_slice(&(rom.len() as u32).to_le_bytes());
+        assert_eq!(extract(data.clone(), None).unwrap(), rom);
+
+        let len = data.len();
+        data[len - 8] ^= 1;
+        assert!(matches!(
+            extract(data, None),
+            Err(ArchiveError::ChecksumMismatch { .. })
+        ));
+
+        /* 不是压缩文件时原样返回 */
+        assert_eq!(extract(rom.clone(), None).unwrap(), rom);
+    }
+
+    #[test]
+    fn inflate_limited_to_largest_rom() {
+        let rom = vec![0; MAX_ROM_SIZE];
+        assert_eq!(
+            inflate(&compress_to_vec(&rom, 1)).unwrap().len(),
+            MAX_ROM_SIZE
+        );
+        let oversized = vec![0; MAX_ROM_SIZE + 1];
+        assert_eq!(
+            inflate(&compress_to_vec(&oversized, 1)),
+            Err(ArchiveError::Malformed("invalid deflate stream"))
+        );
+    }
+
+    #[test]
+    fn truncated_or_malformed_archives() {
+        let truncated = Err(ArchiveError::Malformed("unexpected end of archive"));
+        assert_eq!(extract(vec![0x1F, 0x8B, 8], None), truncated);
+        assert_eq!(
+            extract(vec![0x1F, 0x8B, 8, 0x08, 0, 0, 0, 0, 0, 3], None),
+            truncated
+        );
+        assert_eq!(
+            extract(
+                vec![0x1F, 0x8B, 8, 0x04, 0, 0, 0, 0, 0, 3, 0xFF, 0xFF],
+                None
+            ),
+            truncated
+        );
+
+        let data = zip(&[("game.gb", &[0x22; 0x100], false)]);
+        assert_eq!(
+            extract(data[..data.len() - 22].to_vec(), None),
+            Err(ArchiveError::Malformed(
+                "end of central directory not found"
+            ))
+        );
+        /* central directory 中的偏移和大小超出文件范围 */
+        let directory = data.len() - 22 - 46 - 7;
+        let mut bad_offset = data.clone();
+        bad_offset[data.len() - 6..data.len() - 2].copy_from_slice(&[0xFF; 4]);
+        assert_eq!(extract(bad_offset, None), truncated);
+        let mut bad_size = data.clone();
+        bad_size[directory + 20..directory + 24].copy_from_slice(&[0xFF; 4]);
+        assert_eq!(extract(bad_size, None), truncated);
+        let mut bad_name = data.clone();
+        bad_name[directory + 28..directory + 30].copy_from_slice(&[0xFF; 2]);
+        assert_eq!(extract(bad_name, None), truncated);
+        let mut bad_header = data;
+        bad_header[directory + 42..directory + 46].copy_from_slice(&[0xF0, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(extract(bad_header, None), truncated);
+    }
+}
@@ -0,0 +1,105 @@
+use super::header::NINTENDO_LOGO;
+use super::mapper::{Capabilities, Mapper};
+use super::mbc1::Mbc1;
+
+/**
+ * 常见的 MBC1 盗版多合一卡带：多个 MBC1 游戏以 32KB 对齐依次存放，
+ * 菜单向 0x7000-0x7FFF 写入所选游戏的起始位置（以 32KB 为单位）后锁定，
+ * 之后游戏看到的是一个只有自己 ROM 大小的普通 MBC1
+ */
+pub struct Mbc1Bootleg {
+    inner: Mbc1,
+    outer_bank: usize,
+    locked: bool,
+}
+
+impl Mbc1Bootleg {
+    pub fn new(ram_size: usize, battery: bool) -> Self {
+        Mbc1Bootleg {
+            inner: Mbc1::new(ram_size, false, battery),
+            outer_bank: 0,
+            locked: false,
+        }
+    }
+
+    /*
+     * 在第一个游戏声明的 ROM 大小之后还能找到其他游戏的 header，
+     * 与第一个 header 相同的是 overdump 时重复的镜像
+     */
+    pub fn detect(rom: &[u8], rom_size: usize) -> bool {
+        rom_size >= 0x8000
+            && rom.len() > rom_size
+            && (rom_size..rom.len() - 0x150).step_by(0x8000).any(|offset| {
+                rom[offset + 0x104..offset + 0x134] == NINTENDO_LOGO
+                    && rom[offset + 0x134..offset + 0x150] != rom[0x134..0x150]
+            })
+    }
+
+    /* 当前游戏的 ROM，大小取自游戏自己的 header */
+    fn game<'a>(&self, rom: &'a [u8]) -> &'a [u8] {
+        let base = (self.outer_bank * 0x8000) % rom.len();
+        let size = match rom.get(base + 0x148) {
+            Some(&code @ 0x00..=0x08) => 0x8000 << code,
+            _ => 0x8000,
+        };
+        &rom[base..rom.len().min(base + size)]
+    }
+}
+
+impl Mapper for Mbc1Bootleg {
+    fn read(&self, rom: &[u8], address: u16) -> u8 {
+        self.inner.read(self.game(rom), address)
+    }
+
+    fn write(&mut self, address: u16, value: u8) -> bool {
+        if let (0x7000..=0x7FFF, false) = (address, self.locked) {
+            self.outer_bank = value as usize;
+            self.locked = true;
+            return false;
+        }
+        self.inner.write(address, value)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.inner.save_data()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        self.inner.load_save_data(data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn menu_selects_game() {
+        /* 32KB 的菜单之后是一个 64KB 的游戏 */
+        let mut rom: Vec<u8> = (0..8).flat_map(|bank| vec![bank; 0x4000]).collect();
+        rom[0x8104..0x8134].copy_from_slice(&NINTENDO_LOGO);
+        rom[0x8148] = 0x01;
+        assert!(Mbc1Bootleg::detect(&rom, 0x8000));
+        assert!(!Mbc1Bootleg::detect(&rom, 0x20000));
+        rom[0x104..0x134].copy_from_slice(&NINTENDO_LOGO);
+        rom.copy_within(0x8134..0x8150, 0x134);
+        assert!(!Mbc1Bootleg::detect(&rom, 0x8000));
+        rom[0x134] = b'M';
+
+        let mut mbc = Mbc1Bootleg::new(0, false);
+        assert_eq!(mbc.read(&rom, 0x4000), 1);
+        mbc.write(0x7000, 0x01);
+        assert_eq!(mbc.read(&rom, 0x0000), 2);
+        assert_eq!(mbc.read(&rom, 0x4000), 3);
+
+        /* 游戏内的 bank 切换被限制在游戏自己的 ROM 内，且选择已锁定 */
+        mbc.write(0x2000, 0x05);
+        assert_eq!(mbc.read(&rom, 0x4000), 3);
+        mbc.write(0x7000, 0x00);
+        assert_eq!(mbc.read(&rom, 0x0000), 2);
+    }
+}
@@ -0,0 +1,512 @@
+use super::mapper::{Capabilities, Mapper};
+use crate::utils::bit;
+
+pub const SENSOR_WIDTH: usize = 128;
+/* 传感器比最终图像多出 8 行，处理时上下各裁掉 4 行 */
+pub const SENSOR_HEIGHT: usize = 120;
+pub const IMAGE_WIDTH: usize = 128;
+pub const IMAGE_HEIGHT: usize = 112;
+
+/* 宿主图像来源，返回灰度或 RGBA 像素以及宽、高 */
+pub type ImageSource = Box<dyn Fn() -> Option<(Vec<u8>, usize, usize)>>;
+
+const EDGE_RATIO: [f32; 8] = [0.50, 0.75, 1.00, 1.25, 2.00, 3.00, 4.00, 5.00];
+/* MAC-GBD 的 ADC 满量程电压，对应读数 0-255 */
+const ADC_FULL_SCALE: f32 = 4.0;
+
+/* 传感器增益 G (A001 bit 0-4)：14.0dB 起每级 1.5dB，G4 为 1 时再加 6dB */
+fn gain_db(register: u8) -> f32 {
+    14.0 + 1.5 * (register & 0x0F) as f32 + if bit!(register, 4) { 6.0 } else { 0.0 }
+}
+
+/**
+ * 输出参考电压换算成的 ADC 读数：V (A004 bit 0-2) 每级 0.5V，
+ * O (A005 bit 0-4) 每级 32mV，bit 5 为 1 时为负
+ */
+fn output_reference(registers: &[u8]) -> f32 {
+    let v = (registers[4] & 0x07) as f32 * 0.5;
+    let o = (registers[5] & 0x1F) as f32 * 0.032;
+    let volts = if bit!(registers[5], 5) { v - o } else { v + o };
+    volts * 256.0 / ADC_FULL_SCALE
+}
+
+/**
+ * 把宿主提供的灰度或 RGBA 图像缩放成 128x120 的传感器输入
+ */
+pub fn sensor_image_from(data: &[u8], width: usize, height: usize) -> Option<Vec<u8>> {
+    let channels = match data.len() {
+        0 => return None,
+        len if len == width * height => 1,
+        len if len == width * height * 4 => 4,
+        _ => return None,
+    };
+    let mut image = vec![0; SENSOR_WIDTH * SENSOR_HEIGHT];
+    for y in 0..SENSOR_HEIGHT {
+        for x in 0..SENSOR_WIDTH {
+            let src = ((y * height / SENSOR_HEIGHT) * width + x * width / SENSOR_WIDTH) * channels;
+            image[y * SENSOR_WIDTH + x] = match channels {
+                1 => data[src],
+                _ => {
+                    let (r, g, b) = (data[src] as u32, data[src + 1] as u32, data[src + 2] as u32);
+                    ((r * 299 + g * 587 + b * 114) / 1000) as u8
+                }
+            };
+        }
+    }
+    Some(image)
+}
+
+/**
+ * Game Boy Camera (MAC-GBD + M64282FP)
+ */
+pub struct Camera {
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+    /* RAM bank 寄存器第 4 位为 1 时 0xA000-0xBFFF 映射为传感器寄存器 */
+    registers_mapped: bool,
+    registers: [u8; 0x36],
+    /* 剩余的拍摄 M-cycle，为 0 时表示空闲 */
+    capture_cycles: u32,
+    image: Vec<u8>,
+    source: Option<ImageSource>,
+}
+
+impl Camera {
+    pub fn new(ram_size: usize) -> Self {
+        Camera {
+            ram: vec![0; ram_size.max(0x20000)],
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            registers_mapped: false,
+            registers: [0; 0x36],
+            capture_cycles: 0,
+            image: vec![0x80; SENSOR_WIDTH * SENSOR_HEIGHT],
+            source: None,
+        }
+    }
+
+    fn start_capture(&mut self) {
+        let image = self.source.as_ref().and_then(|source| source());
+        if let Some(image) = image.and_then(|(data, w, h)| sensor_image_from(&data, w, h)) {
+            self.image = image;
+        }
+        let n = bit!(self.registers[1], 7);
+        let exposure = (self.registers[2] as u32) << 8 | self.registers[3] as u32;
+        self.capture_cycles = 32446 + if n { 0 } else { 512 } + 16 * exposure;
+    }
+
+    /**
+     * 模拟传感器的曝光、边缘增强以及控制器的抖动矩阵，结果以 tile 格式写入 RAM bank 0 的 0x0100
+     */
+    fn capture(&mut self) {
+        let registers = &self.registers;
+        let (p, m) = match (registers[0] >> 1) & 3 {
+            0 => (0b00, 0b01),
+            1 => (0b01, 0b00),
+            _ => (0b01, 0b10),
+        };
+        let n = bit!(registers[1], 7);
+        let vh = (registers[1] >> 5) & 3;
+        let exposure = ((registers[2] as u32) << 8 | registers[3] as u32) as f32 / 0x300 as f32;
+        let gain = 10f32.powf((gain_db(registers[1]) - gain_db(0)) / 20.0);
+        let reference = output_reference(registers);
+        let alpha = EDGE_RATIO[(registers[4] as usize >> 4) & 7];
+        let e3 = bit!(registers[4], 7);
+        let invert = bit!(registers[4], 3);
+
+        let index = |x: usize, y: usize| y * SENSOR_WIDTH + x;
+        let mut retina: Vec<i32> = self
+            .image
+            .iter()
+            .map(|&value| {
+                /* 最小增益、曝光 0x0300 且参考电压为 0 时，读数与输入图像相同 */
+                let value = reference + value as f32 * exposure * gain;
+                let value = (value.round() as i32).clamp(0, 255);
+                if invert {
+                    127 - value
+                } else {
+                    value - 128
+                }
+            })
+            .collect();
+
+        /* 1-D 滤波：P/M 位选择当前像素与下方像素的加减组合 */
+        let filter_1d = |source: &[i32], target: &mut [i32]| {
+            for y in 0..SENSOR_HEIGHT {
+                for x in 0..SENSOR_WIDTH {
+                    let px = source[index(x, y)];
+                    let ms = source[index(x, (y + 1).min(SENSOR_HEIGHT - 1))];
+                    let mut value = 0;
+                    if p & 1 != 0 {
+                        value += px;
+                    }
+                    if p & 2 != 0 {
+                        value += ms;
+                    }
+                    if m & 1 != 0 {
+                        value -= px;
+                    }
+                    if m & 2 != 0 {
+                        value -= ms;
+                    }
+                    target[index(x, y)] = value.clamp(-128, 127);
+                }
+            }
+        };
+
+        let mut temp = vec![0; retina.len()];
+        match (n as u8) << 3 | vh << 1 | e3 as u8 {
+            0x0 => {
+                temp.copy_from_slice(&retina);
+                filter_1d(&temp, &mut retina);
+            }
+            /* 水平边缘增强：P + (2P - (MW + ME)) * alpha */
+            0x2 => {
+                for y in 0..SENSOR_HEIGHT {
+                    for x in 0..SENSOR_WIDTH {
+                        let px = retina[index(x, y)];
+                        let mw = retina[index(x.saturating_sub(1), y)];
+                        let me = retina[index((x + 1).min(SENSOR_WIDTH - 1), y)];
+                        let value = px as f32 + (2 * px - mw - me) as f32 * alpha;
+                        temp[index(x, y)] = (value as i32).clamp(-128, 127);
+                    }
+                }
+                filter_1d(&temp, &mut retina);
+            }
+            /* 2D 边缘增强：P + (4P - (MN + MS + ME + MW)) * alpha */
+            0xE => {
+                for y in 0..SENSOR_HEIGHT {
+                    for x in 0..SENSOR_WIDTH {
+                        let px = retina[index(x, y)];
+                        let mn = retina[index(x, y.saturating_sub(1))];
+                        let ms = retina[index(x, (y + 1).min(SENSOR_HEIGHT - 1))];
+                        let mw = retina[index(x.saturating_sub(1), y)];
+                        let me = retina[index((x + 1).min(SENSOR_WIDTH - 1), y)];
+                        let value = px as f32 + (4 * px - mn - ms - mw - me) as f32 * alpha;
+                        temp[index(x, y)] = (value as i32).clamp(-128, 127);
+                    }
+                }
+                retina.copy_from_slice(&temp);
+            }
+            /* 实机上这种组合输出恒定的颜色 */
+            0x1 => retina.fill(0),
+            _ => {}
+        }
+
+        let tiles = &mut self.ram[0x100..0x100 + IMAGE_WIDTH * IMAGE_HEIGHT / 4];
+        tiles.fill(0);
+        for y in 0..IMAGE_HEIGHT {
+            for x in 0..IMAGE_WIDTH {
+                let value = retina[index(x, y + (SENSOR_HEIGHT - IMAGE_HEIGHT) / 2)] + 128;
+                /* 4x4 抖动矩阵，每个位置 3 个阈值 */
+                let base = 6 + ((y & 3) * 4 + (x & 3)) * 3;
+                let level = registers[base..base + 3]
+                    .iter()
+                    .take_while(|&&threshold| value >= threshold as i32)
+                    .count();
+                let color = 3 - level;
+
+                let tile = (y >> 3) * (IMAGE_WIDTH / 8) + (x >> 3);
+                let offset = tile * 16 + (y & 7) * 2;
+                let mask = 0x80 >> (x & 7);
+                if color & 1 != 0 {
+                    tiles[offset] |= mask;
+                }
+                if color & 2 != 0 {
+                    tiles[offset + 1] |= mask;
+                }
+            }
+        }
+    }
+
+    /**
+     * 把 RAM 中的 tile 数据还原成 128x112 的颜色编号
+     */
+    pub fn captured_image(&self) -> Vec<u8> {
+        let tiles = &self.ram[0x100..0x100 + IMAGE_WIDTH * IMAGE_HEIGHT / 4];
+        let mut image = vec![0; IMAGE_WIDTH * IMAGE_HEIGHT];
+        for y in 0..IMAGE_HEIGHT {
+            for x in 0..IMAGE_WIDTH {
+                let offset = ((y >> 3) * (IMAGE_WIDTH / 8) + (x >> 3)) * 16 + (y & 7) * 2;
+                let shift = 7 - (x & 7);
+                image[y * IMAGE_WIDTH + x] =
+                    (tiles[offset] >> shift) & 1 | ((tiles[offset + 1] >> shift) & 1) << 1;
+            }
+        }
+        image
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        (self.ram_bank as usize * 0x2000 + (address as usize & 0x1FFF)) % self.ram.len()
+    }
+}
+
+impl Mapper for Camera {
+    fn read(&self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => rom[address as usize],
+            0x4000..=0x7FFF => {
+                let offset = self.rom_bank as usize * 0x4000 + (address as usize & 0x3FFF);
+                rom[offset % rom.len()]
+            }
+            /* 只有 A000 可读，bit 0 为拍摄中标志 */
+            0xA000..=0xBFFF if self.registers_mapped => match address & 0x7F {
+                0x00 => self.registers[0] & 0x07,
+                _ => 0x00,
+            },
+            0xA000..=0xBFFF => self.ram[self.ram_offset(address)],
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) -> bool {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = value & 0x3F,
+            0x4000..=0x5FFF => {
+                self.registers_mapped = bit!(value, 4);
+                self.ram_bank = value & 0x0F;
+            }
+            0xA000..=0xBFFF if self.registers_mapped => match address as usize & 0x7F {
+                0x00 => {
+                    let start = bit!(value, 0) && self.capture_cycles == 0;
+                    self.registers[0] = value & 0x07;
+                    if start {
+                        self.start_capture();
+                    } else if self.capture_cycles != 0 {
+                        self.registers[0] |= 1;
+                    }
+                }
+                register @ 0x01..=0x35 => self.registers[register] = value,
+                _ => {}
+            },
+            0xA000..=0xBFFF if self.ram_enabled => {
+                let offset = self.ram_offset(address);
+                self.ram[offset] = value;
+                return true;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            battery: true,
+            sensor: true,
+            ..Default::default()
+        }
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let len = self.ram.len().min(data.len());
+        self.ram[..len].copy_from_slice(&data[..len]);
+    }
+
+    /**
+     * 设置固定的传感器图像，data 为 128x120 灰度
+     */
+    fn set_camera_image(&mut self, image: Vec<u8>) -> bool {
+        self.image = image;
+        true
+    }
+
+    /**
+     * 每次开始拍摄时从 source 获取最新的传感器图像（如摄像头画面）
+     */
+    fn set_camera_source(&mut self, source: ImageSource) {
+        self.source = Some(source);
+    }
+
+    fn camera_capture(&self) -> Option<Vec<u8>> {
+        Some(self.captured_image())
+    }
+
+    fn tick(&mut self) {
+        if self.capture_cycles == 0 {
+            return;
+        }
+        self.capture_cycles -= 1;
+        if self.capture_cycles == 0 {
+            self.capture();
+            self.registers[0] &= !1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup(image: Vec<u8>) -> Camera {
+        let mut camera = Camera::new(0x20000);
+        camera.set_camera_image(image);
+        camera.write(0x4000, 0x10);
+        /* 常见的拍摄参数：exposure = 0x0300，2D 边缘增强关闭 */
+        camera.write(0xA001, 0x00);
+        camera.write(0xA002, 0x03);
+        camera.write(0xA003, 0x00);
+        camera.write(0xA004, 0x00);
+        for i in 0..16 {
+            camera.write(0xA006 + i * 3, 0x40);
+            camera.write(0xA007 + i * 3, 0x80);
+            camera.write(0xA008 + i * 3, 0xC0);
+        }
+        camera
+    }
+
+    fn capture(camera: &mut Camera) -> Vec<u8> {
+        camera.write(0xA000, 0x03);
+        assert_eq!(camera.read(&[0], 0xA000) & 1, 1);
+        let mut cycles = 0;
+        while camera.read(&[0], 0xA000) & 1 != 0 {
+            camera.tick();
+            cycles += 1;
+        }
+        let n = bit!(camera.registers[1], 7);
+        assert_eq!(cycles, 32446 + if n { 0 } else { 512 } + 16 * 0x300);
+        camera.write(0x4000, 0x00);
+        let tiles = (0x0100..0x0100 + 0xE00)
+            .map(|address| camera.read(&[0], 0xA000 + address))
+            .collect();
+        camera.write(0x4000, 0x10);
+        tiles
+    }
+
+    #[test]
+    fn host_image_conversion() {
+        let rgba: Vec<u8> = [0xFF, 0x00, 0x00, 0xFF].repeat(4);
+        let image = sensor_image_from(&rgba, 2, 2).unwrap();
+        assert_eq!(image.len(), SENSOR_WIDTH * SENSOR_HEIGHT);
+        assert!(image.iter().all(|&value| value == 76));
+
+        let gray: Vec<u8> = (0..=255).collect();
+        let image = sensor_image_from(&gray, 256, 1).unwrap();
+        assert_eq!(&image[0..3], &[0, 2, 4]);
+        assert_eq!(image[SENSOR_WIDTH * 119 + 127], 254);
+
+        assert_eq!(sensor_image_from(&gray, 10, 10), None);
+    }
+
+    #[test]
+    fn capture_uniform_images() {
+        /* 默认参数下白色与黑色分别越过全部 / 没有越过任何阈值 */
+        let mut camera = setup(vec![0xFF; SENSOR_WIDTH * SENSOR_HEIGHT]);
+        assert!(capture(&mut camera)
+            .chunks(2)
+            .all(|plane| plane == [0x00, 0x00]));
+
+        camera.set_camera_image(vec![0x00; SENSOR_WIDTH * SENSOR_HEIGHT]);
+        assert!(capture(&mut camera)
+            .chunks(2)
+            .all(|plane| plane == [0xFF, 0xFF]));
+    }
+
+    #[test]
+    fn capture_gain_and_output_reference() {
+        /* 0x50 在默认增益下读数为 80：color = 2 */
+        let mut camera = setup(vec![0x50; SENSOR_WIDTH * SENSOR_HEIGHT]);
+        capture(&mut camera);
+        assert!(camera.captured_image().iter().all(|&color| color == 2));
+
+        /* G = 4 增益 +6dB：80 * 1.995 = 160，color = 1 */
+        camera.write(0xA001, 0x04);
+        capture(&mut camera);
+        assert!(camera.captured_image().iter().all(|&color| color == 1));
+        /* G4 同样 +6dB */
+        camera.write(0xA001, 0x10);
+        capture(&mut camera);
+        assert!(camera.captured_image().iter().all(|&color| color == 1));
+
+        /* V = 2 (1.0V) 把黑色抬高到 64：color = 2 */
+        camera.write(0xA001, 0x00);
+        camera.set_camera_image(vec![0x00; SENSOR_WIDTH * SENSOR_HEIGHT]);
+        camera.write(0xA004, 0x02);
+        capture(&mut camera);
+        assert!(camera.captured_image().iter().all(|&color| color == 2));
+
+        /* O = -16 (-0.512V)：64 - 32.8 = 31，color = 3 */
+        camera.write(0xA005, 0x30);
+        capture(&mut camera);
+        assert!(camera.captured_image().iter().all(|&color| color == 3));
+    }
+
+    #[test]
+    fn capture_dithering_follows_matrix_position() {
+        let mut camera = setup(vec![0x80; SENSOR_WIDTH * SENSOR_HEIGHT]);
+        /* 把 (0,0) 位置的阈值调高，使该位置输出最暗的颜色 */
+        camera.write(0xA006, 0xF0);
+        camera.write(0xA007, 0xF0);
+        camera.write(0xA008, 0xF0);
+        let tiles = capture(&mut camera);
+        assert_eq!(&tiles[0..2], &[0xFF, 0x88]);
+        assert_eq!(&tiles[2..4], &[0xFF, 0x00]);
+        assert_eq!(&tiles[8..10], &[0xFF, 0x88]);
+
+        let image = camera.captured_image();
+        assert_eq!(image.len(), IMAGE_WIDTH * IMAGE_HEIGHT);
+        assert_eq!(&image[0..8], &[3, 1, 1, 1, 3, 1, 1, 1]);
+        assert_eq!(&image[IMAGE_WIDTH..IMAGE_WIDTH + 4], &[1, 1, 1, 1]);
+    }
+
+    /* 左半边 0x40、右半边 0xA0，读数减去 128 后分别为 -64 和 32 */
+    fn capture_edge(register1: u8, register4: u8) -> Vec<u8> {
+        let image = (0..SENSOR_WIDTH * SENSOR_HEIGHT)
+            .map(|i| if i % SENSOR_WIDTH < 64 { 0x40 } else { 0xA0 })
+            .collect();
+        let mut camera = setup(image);
+        camera.write(0xA001, register1);
+        camera.write(0xA004, register4);
+        capture(&mut camera);
+        camera.captured_image()[60..68].to_vec()
+    }
+
+    #[test]
+    fn capture_with_edge_enhancement() {
+        /*
+         * 2D 增强，alpha = 4：x = 63 为 -64 + (-256 + 192 - 32) * 4，下溢到 -128；
+         * x = 64 为 32 + (128 + 64 - 32 + 64 - 64) * 4，上溢到 127
+         */
+        assert_eq!(capture_edge(0xE0, 0x60), [2, 2, 2, 3, 0, 1, 1, 1]);
+        /*
+         * 水平增强，alpha = 0.5：x = 63 为 -64 + (-128 + 64 - 32) / 2 = -112，
+         * x = 64 为 32 + (64 + 64 - 32) / 2 = 80
+         */
+        assert_eq!(capture_edge(0x20, 0x00), [2, 2, 2, 3, 0, 1, 1, 1]);
+    }
+
+    /**
+     * fixtures/camera_2d_edge.2bpp 由按 Pan Docs 描述独立实现的参考模型生成：
+     * 输入为 (x * 2 + y) % 256 的渐变，(48, 40) 处叠加 32x32 的 0xF0 方块，
+     * 2D 增强 alpha = 1，位置 i 的阈值为 0x20 / 0x70 / 0xB0 + i * 4
+     */
+    #[test]
+    fn capture_matches_fixture() {
+        let image = (0..SENSOR_HEIGHT)
+            .flat_map(|y| (0..SENSOR_WIDTH).map(move |x| (x, y)))
+            .map(|(x, y)| match (x, y) {
+                (48..=79, 40..=71) => 0xF0,
+                _ => ((x * 2 + y) % 256) as u8,
+            })
+            .collect();
+        let mut camera = setup(image);
+        camera.write(0xA001, 0xE0);
+        camera.write(0xA004, 0x20);
+        for i in 0..16 {
+            camera.write(0xA006 + i * 3, 0x20 + i as u8 * 4);
+            camera.write(0xA007 + i * 3, 0x70 + i as u8 * 4);
+            camera.write(0xA008 + i * 3, 0xB0 + i as u8 * 4);
+        }
+        assert_eq!(
+            capture(&mut camera),
+            include_bytes!("../../fixtures/camera_2d_edge.2bpp")
+        );
+    }
+}
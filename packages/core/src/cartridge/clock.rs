@@ -0,0 +1,46 @@
+/**
+ * 宿主时钟，供卡带上的 RTC 使用，测试时可以注入固定的时间
+ */
+pub trait Clock {
+    /* unix 时间戳（秒） */
+    fn now(&self) -> u64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[cfg(target_arch = "wasm32")]
+    fn now(&self) -> u64 {
+        (js_sys::Date::now() / 1000.0) as u64
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn now(&self) -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::Clock;
+
+    #[derive(Clone, Default)]
+    pub struct FakeClock(pub Rc<Cell<u64>>);
+
+    impl FakeClock {
+        pub fn advance(&self, seconds: u64) {
+            self.0.set(self.0.get() + seconds);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> u64 {
+            self.0.get()
+        }
+    }
+}
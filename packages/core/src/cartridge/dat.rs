@@ -0,0 +1,157 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DumpStatus {
+    Good,
+    Verified,
+    /* DAT 中标记为 baddump，已知有错误的 dump */
+    BadDump,
+}
+
+/**
+ * DAT 中的一个 ROM
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatEntry {
+    pub name: String,
+    pub region: Option<String>,
+    pub size: Option<usize>,
+    pub crc32: Option<u32>,
+    pub sha1: Option<[u8; 20]>,
+    pub status: DumpStatus,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatError(String);
+
+impl std::fmt::Display for DatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid DAT file: {}", self.0)
+    }
+}
+
+impl std::error::Error for DatError {}
+
+/**
+ * No-Intro / Logiqx 格式的 XML DAT，用 CRC32 与 SHA-1 识别 ROM
+ */
+#[derive(Debug, Default)]
+pub struct Dat {
+    entries: Vec<DatEntry>,
+}
+
+fn parse_sha1(hex: &str) -> Option<[u8; 20]> {
+    let mut sha1 = [0; 20];
+    if hex.len() != 40 {
+        return None;
+    }
+    for (i, byte) in sha1.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(sha1)
+}
+
+/* No-Intro 的名字中第一个括号内为地区，如 "Tetris (World) (Rev 1)" */
+fn region_from_name(name: &str) -> Option<String> {
+    let start = name.find(" (")? + 2;
+    let end = start + name[start..].find(')')?;
+    Some(name[start..end].to_string())
+}
+
+impl Dat {
+    pub fn parse(xml: &str) -> Result<Self, DatError> {
+        /* No-Intro 的 DAT 带有 DOCTYPE */
+        let options = roxmltree::ParsingOptions {
+            allow_dtd: true,
+            ..Default::default()
+        };
+        let document = roxmltree::Document::parse_with_options(xml, options)
+            .map_err(|err| DatError(err.to_string()))?;
+        let root = document.root_element();
+        if root.tag_name().name() != "datafile" {
+            return Err(DatError(format!(
+                "unexpected root element <{}>",
+                root.tag_name().name()
+            )));
+        }
+        let mut entries = Vec::new();
+        for game in root
+            .children()
+            .filter(|node| matches!(node.tag_name().name(), "game" | "machine"))
+        {
+            let name = game.attribute("name").unwrap_or_default();
+            let region = game
+                .children()
+                .find(|node| node.has_tag_name("release"))
+                .and_then(|release| release.attribute("region"))
+                .map(String::from)
+                .or_else(|| region_from_name(name));
+            for rom in game.children().filter(|node| node.has_tag_name("rom")) {
+                entries.push(DatEntry {
+                    name: name.to_string(),
+                    region: region.clone(),
+                    size: rom.attribute("size").and_then(|size| size.parse().ok()),
+                    crc32: rom
+                        .attribute("crc")
+                        .and_then(|crc| u32::from_str_radix(crc, 16).ok()),
+                    sha1: rom.attribute("sha1").and_then(parse_sha1),
+                    status: match rom.attribute("status") {
+                        Some("baddump") => DumpStatus::BadDump,
+                        Some("verified") => DumpStatus::Verified,
+                        _ => DumpStatus::Good,
+                    },
+                });
+            }
+        }
+        Ok(Dat { entries })
+    }
+
+    /**
+     * 优先按 SHA-1 匹配，DAT 中没有 SHA-1 时按 CRC32 与大小匹配
+     */
+    pub fn lookup(&self, size: usize, crc32: u32, sha1: &[u8; 20]) -> Option<&DatEntry> {
+        self.entries.iter().find(|entry| match entry.sha1 {
+            Some(entry_sha1) => entry_sha1 == *sha1,
+            None => entry.crc32 == Some(crc32) && entry.size.is_none_or(|s| s == size),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAT: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE datafile PUBLIC "-//Logiqx//DTD ROM Management Datafile//EN" "http://www.logiqx.com/dtds/datafile.dtd">
+<datafile>
+    <header>
+        <name>Nintendo - Game Boy</name>
+    </header>
+    <game name="Tetris (World) (Rev 1)">
+        <description>Tetris (World) (Rev 1)</description>
+        <rom name="Tetris (World) (Rev 1).gb" size="32768" crc="46df91ad" sha1="74591cc9501af93873f9a5d3eb12da12c0723bbc" status="verified"/>
+    </game>
+    <game name="Some Game (Japan)">
+        <release name="Some Game" region="JPN"/>
+        <rom name="Some Game (Japan).gb" size="4" crc="CBF43926" status="baddump"/>
+    </game>
+</datafile>"#;
+
+    #[test]
+    fn lookup_by_sha1_and_crc32() {
+        let dat = Dat::parse(DAT).unwrap();
+        assert_eq!(dat.entries.len(), 2);
+
+        let sha1 = parse_sha1("74591cc9501af93873f9a5d3eb12da12c0723bbc").unwrap();
+        let entry = dat.lookup(0x8000, 0, &sha1).unwrap();
+        assert_eq!(entry.name, "Tetris (World) (Rev 1)");
+        assert_eq!(entry.region.as_deref(), Some("World"));
+        assert_eq!(entry.status, DumpStatus::Verified);
+
+        let entry = dat.lookup(4, 0xCBF43926, &[0; 20]).unwrap();
+        assert_eq!(entry.region.as_deref(), Some("JPN"));
+        assert_eq!(entry.status, DumpStatus::BadDump);
+        assert_eq!(dat.lookup(5, 0xCBF43926, &[0; 20]), None);
+
+        assert!(Dat::parse("<html></html>").is_err());
+        assert!(Dat::parse("not xml").is_err());
+    }
+}
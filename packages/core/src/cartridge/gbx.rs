@@ -0,0 +1,139 @@
+use super::mapper::{MapperConfig, MapperKind};
+use super::CartridgeError;
+
+/* footer 最后 16 字节：footer 大小、主版本号、次版本号、magic，均为大端序 */
+const TRAILER_SIZE: usize = 16;
+
+/**
+ * GBX 格式附加在 ROM 末尾的 footer，声明了 mapper 与卡带上的硬件，
+ * 优先于（经常写错的）header
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct GbxFooter {
+    /* 4 个 ASCII 字符，不足 4 个时以 0 补齐 */
+    pub mapper: [u8; 4],
+    pub battery: bool,
+    pub rumble: bool,
+    pub rtc: bool,
+    pub rom_size: usize,
+    pub ram_size: usize,
+    /* footer 的总大小，加载时从 ROM 末尾去掉 */
+    pub size: usize,
+}
+
+fn u32_at(data: &[u8], offset: usize) -> usize {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize
+}
+
+impl GbxFooter {
+    /**
+     * 没有 footer 或版本不是 1.x 时返回 None
+     */
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let trailer = data.len().checked_sub(TRAILER_SIZE)?;
+        if &data[trailer + 12..] != b"GBX!" || u32_at(data, trailer + 4) != 1 {
+            return None;
+        }
+        let size = u32_at(data, trailer);
+        let start = data.len().checked_sub(size)?;
+        if size < TRAILER_SIZE + 16 {
+            return None;
+        }
+        let footer = &data[start..];
+        Some(GbxFooter {
+            mapper: footer[0..4].try_into().unwrap(),
+            battery: footer[4] != 0,
+            rumble: footer[5] != 0,
+            rtc: footer[6] != 0,
+            rom_size: u32_at(footer, 8),
+            ram_size: u32_at(footer, 12),
+            size,
+        })
+    }
+
+    pub fn mapper_name(&self) -> String {
+        String::from_utf8_lossy(&self.mapper)
+            .trim_end_matches('\0')
+            .to_string()
+    }
+
+    pub fn config(&self) -> Result<MapperConfig, CartridgeError> {
+        let kind = match &self.mapper {
+            b"ROM\0" => MapperKind::RomOnly,
+            b"MBC1" => MapperKind::Mbc1,
+            b"MB1M" => MapperKind::Mbc1Multicart,
+            b"MBC2" => MapperKind::Mbc2,
+            b"MBC3" => MapperKind::Mbc3,
+            b"MBC5" => MapperKind::Mbc5,
+            b"MBC7" => MapperKind::Mbc7,
+            b"MMM1" => MapperKind::Mmm01,
+            b"CAMR" => MapperKind::Camera,
+            b"TAM5" => MapperKind::Tama5,
+            b"HUC1" => MapperKind::HuC1,
+            b"HUC3" => MapperKind::HuC3,
+            b"WISD" => MapperKind::WisdomTree,
+            b"SAM1" => MapperKind::SachenMmc1,
+            b"SAM2" => MapperKind::SachenMmc2,
+            _ => return Err(CartridgeError::UnsupportedMapper(self.mapper_name())),
+        };
+        Ok(self.config_with_kind(kind))
+    }
+
+    /* 手动指定 mapper 时只使用 footer 中的 RAM 大小与硬件信息，不检查 mapper 名字 */
+    pub fn config_with_kind(&self, kind: MapperKind) -> MapperConfig {
+        MapperConfig {
+            kind,
+            ram_size: self.ram_size,
+            battery: self.battery,
+            rtc: self.rtc,
+            rumble: self.rumble,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn footer(mapper: &[u8; 4], flags: [u8; 3], rom_size: u32, ram_size: u32) -> Vec<u8> {
+        let mut footer = mapper.to_vec();
+        footer.extend_from_slice(&flags);
+        footer.push(0);
+        footer.extend_from_slice(&rom_size.to_be_bytes());
+        footer.extend_from_slice(&ram_size.to_be_bytes());
+        footer.extend_from_slice(&[0; 32]);
+        footer.extend_from_slice(&0x40u32.to_be_bytes());
+        footer.extend_from_slice(&1u32.to_be_bytes());
+        footer.extend_from_slice(&0u32.to_be_bytes());
+        footer.extend_from_slice(b"GBX!");
+        footer
+    }
+
+    #[test]
+    fn parse_footer() {
+        let mut data = vec![0; 0x8000];
+        data.extend_from_slice(&footer(b"MBC3", [1, 0, 1], 0x8000, 0x2000));
+        let footer = GbxFooter::parse(&data).unwrap();
+        assert_eq!(footer.size, 0x40);
+        assert_eq!(footer.mapper_name(), "MBC3");
+        assert_eq!(
+            footer.config().unwrap(),
+            MapperConfig {
+                kind: MapperKind::Mbc3,
+                ram_size: 0x2000,
+                battery: true,
+                rtc: true,
+                rumble: false,
+            }
+        );
+
+        let mut data = vec![0; 0x8000];
+        data.extend_from_slice(&tests::footer(b"NNL\0", [0, 0, 0], 0x8000, 0));
+        assert_eq!(
+            GbxFooter::parse(&data).unwrap().config(),
+            Err(CartridgeError::UnsupportedMapper("NNL".into()))
+        );
+
+        assert_eq!(GbxFooter::parse(&[0; 0x8000]), None);
+    }
+}
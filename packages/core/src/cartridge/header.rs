@@ -0,0 +1,522 @@
+use super::CartridgeError;
+use crate::utils::array;
+
+pub static CARTRIDGE_TYPE: [&'static str; 256] = array!["Unknown"; 256;
+  [0x00] = "ROM ONLY",
+  [0x01] = "MBC1",
+  [0x02] = "MBC1+RAM",
+  [0x03] = "MBC1+RAM+BATTERY",
+  [0x05] = "MBC2",
+  [0x06] = "MBC2+BATTERY",
+  [0x08] = "ROM+RAM 9",
+  [0x09] = "ROM+RAM+BATTERY 9",
+  [0x0B] = "MMM01",
+  [0x0C] = "MMM01+RAM",
+  [0x0D] = "MMM01+RAM+BATTERY",
+  [0x0F] = "MBC3+TIMER+BATTERY",
+  [0x10] = "MBC3+TIMER+RAM+BATTERY 10",
+  [0x11] = "MBC3",
+  [0x12] = "MBC3+RAM 10",
+  [0x13] = "MBC3+RAM+BATTERY 10",
+  [0x19] = "MBC5",
+  [0x1A] = "MBC5+RAM",
+  [0x1B] = "MBC5+RAM+BATTERY",
+  [0x1C] = "MBC5+RUMBLE",
+  [0x1D] = "MBC5+RUMBLE+RAM",
+  [0x1E] = "MBC5+RUMBLE+RAM+BATTERY",
+  [0x20] = "MBC6",
+  [0x22] = "MBC7+SENSOR+RUMBLE+RAM+BATTERY",
+  [0xFC] = "POCKET CAMERA",
+  [0xFD] = "BANDAI TAMA5",
+  [0xFE] = "HuC3",
+  [0xFF] = "HuC1+RAM+BATTERY",
+];
+
+pub static NINTENDO_LOGO: [u8; 0x30] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+static OLD_LICENSEE_CODE: [&str; 256] = array!["Unknown"; 256;
+  [0x00] = "None",
+  [0x01] = "Nintendo",
+  [0x08] = "Capcom",
+  [0x09] = "HOT-B",
+  [0x0A] = "Jaleco",
+  [0x0B] = "Coconuts Japan",
+  [0x0C] = "Elite Systems",
+  [0x13] = "EA (Electronic Arts)",
+  [0x18] = "Hudson Soft",
+  [0x19] = "ITC Entertainment",
+  [0x1A] = "Yanoman",
+  [0x1D] = "Japan Clary",
+  [0x1F] = "Virgin Games Ltd.",
+  [0x24] = "PCM Complete",
+  [0x25] = "San-X",
+  [0x28] = "Kemco",
+  [0x29] = "SETA Corporation",
+  [0x30] = "Infogrames",
+  [0x31] = "Nintendo",
+  [0x32] = "Bandai",
+  [0x34] = "Konami",
+  [0x35] = "HectorSoft",
+  [0x38] = "Capcom",
+  [0x39] = "Banpresto",
+  [0x3C] = "Entertainment Interactive",
+  [0x3E] = "Gremlin",
+  [0x41] = "Ubi Soft",
+  [0x42] = "Atlus",
+  [0x44] = "Malibu Interactive",
+  [0x46] = "Angel",
+  [0x47] = "Spectrum HoloByte",
+  [0x49] = "Irem",
+  [0x4A] = "Virgin Games Ltd.",
+  [0x4D] = "Malibu Interactive",
+  [0x4F] = "U.S. Gold",
+  [0x50] = "Absolute",
+  [0x51] = "Acclaim Entertainment",
+  [0x52] = "Activision",
+  [0x53] = "Sammy USA Corporation",
+  [0x54] = "GameTek",
+  [0x55] = "Park Place",
+  [0x56] = "LJN",
+  [0x57] = "Matchbox",
+  [0x59] = "Milton Bradley Company",
+  [0x5A] = "Mindscape",
+  [0x5B] = "Romstar",
+  [0x5C] = "Naxat Soft",
+  [0x5D] = "Tradewest",
+  [0x60] = "Titus Interactive",
+  [0x61] = "Virgin Games Ltd.",
+  [0x67] = "Ocean Software",
+  [0x69] = "EA (Electronic Arts)",
+  [0x6E] = "Elite Systems",
+  [0x6F] = "Electro Brain",
+  [0x70] = "Infogrames",
+  [0x71] = "Interplay Entertainment",
+  [0x72] = "Broderbund",
+  [0x73] = "Sculptured Software",
+  [0x75] = "The Sales Curve Limited",
+  [0x78] = "THQ",
+  [0x79] = "Accolade",
+  [0x7A] = "Triffix Entertainment",
+  [0x7C] = "MicroProse",
+  [0x7F] = "Kemco",
+  [0x80] = "Misawa Entertainment",
+  [0x83] = "LOZC G.",
+  [0x86] = "Tokuma Shoten",
+  [0x8B] = "Bullet-Proof Software",
+  [0x8C] = "Vic Tokai Corp.",
+  [0x8E] = "Ape Inc.",
+  [0x8F] = "I'Max",
+  [0x91] = "Chunsoft Co.",
+  [0x92] = "Video System",
+  [0x93] = "Tsubaraya Productions",
+  [0x95] = "Varie",
+  [0x96] = "Yonezawa/S'Pal",
+  [0x97] = "Kemco",
+  [0x99] = "Arc",
+  [0x9A] = "Nihon Bussan",
+  [0x9B] = "Tecmo",
+  [0x9C] = "Imagineer",
+  [0x9D] = "Banpresto",
+  [0x9F] = "Nova",
+  [0xA1] = "Hori Electric",
+  [0xA2] = "Bandai",
+  [0xA4] = "Konami",
+  [0xA6] = "Kawada",
+  [0xA7] = "Takara",
+  [0xA9] = "Technos Japan",
+  [0xAA] = "Broderbund",
+  [0xAC] = "Toei Animation",
+  [0xAD] = "Toho",
+  [0xAF] = "Namco",
+  [0xB0] = "Acclaim Entertainment",
+  [0xB1] = "ASCII Corporation or Nexsoft",
+  [0xB2] = "Bandai",
+  [0xB4] = "Square Enix",
+  [0xB6] = "HAL Laboratory",
+  [0xB7] = "SNK",
+  [0xB9] = "Pony Canyon",
+  [0xBA] = "Culture Brain",
+  [0xBB] = "Sunsoft",
+  [0xBD] = "Sony Imagesoft",
+  [0xBF] = "Sammy Corporation",
+  [0xC0] = "Taito",
+  [0xC2] = "Kemco",
+  [0xC3] = "Square",
+  [0xC4] = "Tokuma Shoten",
+  [0xC5] = "Data East",
+  [0xC6] = "Tonkin House",
+  [0xC8] = "Koei",
+  [0xC9] = "UFL",
+  [0xCA] = "Ultra Games",
+  [0xCB] = "VAP, Inc.",
+  [0xCC] = "Use Corporation",
+  [0xCD] = "Meldac",
+  [0xCE] = "Pony Canyon",
+  [0xCF] = "Angel",
+  [0xD0] = "Taito",
+  [0xD1] = "SOFEL (Software Engineering Lab)",
+  [0xD2] = "Quest",
+  [0xD3] = "Sigma Enterprises",
+  [0xD4] = "ASK Kodansha Co.",
+  [0xD6] = "Naxat Soft",
+  [0xD7] = "Copya System",
+  [0xD9] = "Banpresto",
+  [0xDA] = "Tomy",
+  [0xDB] = "LJN",
+  [0xDD] = "Nippon Computer Systems",
+  [0xDE] = "Human Ent.",
+  [0xDF] = "Altron",
+  [0xE0] = "Jaleco",
+  [0xE1] = "Towa Chiki",
+  [0xE2] = "Yutaka",
+  [0xE3] = "Varie",
+  [0xE5] = "Epoch",
+  [0xE7] = "Athena",
+  [0xE8] = "Asmik Ace Entertainment",
+  [0xE9] = "Natsume",
+  [0xEA] = "King Records",
+  [0xEB] = "Atlus",
+  [0xEC] = "Epic/Sony Records",
+  [0xEE] = "IGS",
+  [0xF0] = "A Wave",
+  [0xF3] = "Extreme Entertainment",
+  [0xFF] = "LJN",
+];
+
+macro_rules! new_licensee_code_array {
+    ($def:expr; $len:expr; $([$idx1:expr,$idx2:expr]=$val:expr),* $(,)?) => { {
+        let mut a = [$def; $len];
+        $(
+            a[($idx2 as usize) << 8 | ($idx1 as usize)] = $val;
+        )*
+        a
+    } }
+}
+
+static NEW_LICENSEE_CODE: [&'static str; 65535] = new_licensee_code_array!["Unknown"; 65535;
+  ['0', '0'] =	"None",
+  ['0', '1'] =	"Nintendo Research & Development 1",
+  ['0', '8'] =	"Capcom",
+  ['1', '3'] =	"EA (Electronic Arts)",
+  ['1', '8'] =	"Hudson Soft",
+  ['1', '9'] =	"B-AI",
+  ['2', '0'] =	"KSS",
+  ['2', '2'] =	"Planning Office WADA",
+  ['2', '4'] =	"PCM Complete",
+  ['2', '5'] =	"San-X",
+  ['2', '8'] =	"Kemco",
+  ['2', '9'] =	"SETA Corporation",
+  ['3', '0'] =	"Viacom",
+  ['3', '1'] =	"Nintendo",
+  ['3', '2'] =	"Bandai",
+  ['3', '3'] =	"Ocean Software/Acclaim Entertainment",
+  ['3', '4'] =	"Konami",
+  ['3', '5'] =	"HectorSoft",
+  ['3', '7'] =	"Taito",
+  ['3', '8'] =	"Hudson Soft",
+  ['3', '9'] =	"Banpresto",
+  ['4', '1'] =	"Ubi Soft1",
+  ['4', '2'] =	"Atlus",
+  ['4', '4'] =	"Malibu Interactive",
+  ['4', '6'] =	"Angel",
+  ['4', '7'] =	"Bullet-Proof Software2",
+  ['4', '9'] =	"Irem",
+  ['5', '0'] =	"Absolute",
+  ['5', '1'] =	"Acclaim Entertainment",
+  ['5', '2'] =	"Activision",
+  ['5', '3'] =	"Sammy USA Corporation",
+  ['5', '4'] =	"Konami",
+  ['5', '5'] =	"Hi Tech Expressions",
+  ['5', '6'] =	"LJN",
+  ['5', '7'] =	"Matchbox",
+  ['5', '8'] =	"Mattel",
+  ['5', '9'] =	"Milton Bradley Company",
+  ['6', '0'] =	"Titus Interactive",
+  ['6', '1'] =	"Virgin Games Ltd.3",
+  ['6', '4'] =	"Lucasfilm Games4",
+  ['6', '7'] =	"Ocean Software",
+  ['6', '9'] =	"EA (Electronic Arts)",
+  ['7', '0'] =	"Infogrames5",
+  ['7', '1'] =	"Interplay Entertainment",
+  ['7', '2'] =	"Broderbund",
+  ['7', '3'] =	"Sculptured Software6",
+  ['7', '5'] =	"The Sales Curve Limited7",
+  ['7', '8'] =	"THQ",
+  ['7', '9'] =	"Accolade",
+  ['8', '0'] =	"Misawa Entertainment",
+  ['8', '3'] =	"lozc",
+  ['8', '6'] =	"Tokuma Shoten",
+  ['8', '7'] =	"Tsukuda Original",
+  ['9', '1'] =	"Chunsoft Co.8",
+  ['9', '2'] =	"Video System",
+  ['9', '3'] =	"Ocean Software/Acclaim Entertainment",
+  ['9', '5'] =	"Varie",
+  ['9', '6'] =	"Yonezawa/s’pal",
+  ['9', '7'] =	"Kaneko",
+  ['9', '9'] =	"Pack-In-Video",
+  ['A', '4'] =	"Konami (Yu-Gi-Oh!)",
+];
+
+/**
+ * CGB flag（0x0143）
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CgbSupport {
+    None,
+    /* 0x80，同时兼容 DMG */
+    Enhanced,
+    /* 0xC0，只能在 CGB 上运行 */
+    Only,
+}
+
+/**
+ * 0x0100-0x014F 处的卡带 header，从 ROM 中复制出来并校验长度
+ */
+#[derive(Clone)]
+pub struct RomHeader {
+    logo: [u8; 0x30],
+    title: [u8; 16],
+    new_licensee_code: [u8; 2],
+    sgb_flag: u8,
+    cart_type: u8,
+    rom_size: u8,
+    ram_size: u8,
+    dest_code: u8,
+    old_licensee_code: u8,
+    version: u8,
+    checksum: u8,
+    global_checksum: u16,
+    /* 按 0x0134-0x014C 计算出的 header checksum */
+    computed_checksum: u8,
+    /* 除 0x014E-0x014F 以外整个 ROM 的字节和 */
+    computed_global_checksum: u16,
+}
+
+impl RomHeader {
+    pub fn parse(data: &[u8]) -> Result<Self, CartridgeError> {
+        Self::parse_with_global_checksum(data, Self::global_checksum(data))
+    }
+
+    /**
+     * header 不在 ROM 开头时（MMM01 菜单、Sachen 打乱的 header），
+     * global checksum 由调用方按整个 ROM 计算后传入
+     */
+    pub fn parse_with_global_checksum(
+        data: &[u8],
+        computed_global_checksum: u16,
+    ) -> Result<Self, CartridgeError> {
+        let header = data
+            .get(0x100..0x150)
+            .ok_or(CartridgeError::TooSmall(data.len()))?;
+        let computed_checksum = header[0x34..=0x4C]
+            .iter()
+            .fold(0u8, |x, byte| x.wrapping_sub(*byte).wrapping_sub(1));
+        Ok(RomHeader {
+            logo: header[0x04..0x34].try_into().unwrap(),
+            title: header[0x34..0x44].try_into().unwrap(),
+            new_licensee_code: [header[0x44], header[0x45]],
+            sgb_flag: header[0x46],
+            cart_type: header[0x47],
+            rom_size: header[0x48],
+            ram_size: header[0x49],
+            dest_code: header[0x4A],
+            old_licensee_code: header[0x4B],
+            version: header[0x4C],
+            checksum: header[0x4D],
+            global_checksum: u16::from_be_bytes([header[0x4E], header[0x4F]]),
+            computed_checksum,
+            computed_global_checksum,
+        })
+    }
+
+    /* 标题以 0 结尾，CGB 卡带的最后一个字节是 CGB flag */
+    pub fn title_str(&self) -> String {
+        let title = match self.title[15] {
+            0x80 | 0xC0 => &self.title[..15],
+            _ => &self.title[..],
+        };
+        let len = title.iter().position(|&c| c == 0).unwrap_or(title.len());
+        String::from_utf8_lossy(&title[..len])
+            .trim_end()
+            .to_string()
+    }
+
+    pub fn cart_type(&self) -> u8 {
+        self.cart_type
+    }
+
+    pub fn cart_type_name(&self) -> &'static str {
+        CARTRIDGE_TYPE[self.cart_type as usize]
+    }
+
+    /* old licensee code 为 0x33 时使用 0x0144-0x0145 处的 new licensee code */
+    pub fn licensee_name(&self) -> &'static str {
+        if self.old_licensee_code != 0x33 {
+            return OLD_LICENSEE_CODE[self.old_licensee_code as usize];
+        }
+        let [code1, code2] = self.new_licensee_code;
+        NEW_LICENSEE_CODE
+            .get((code2 as usize) << 8 | code1 as usize)
+            .copied()
+            .unwrap_or("Unknown")
+    }
+
+    pub fn rom_size_bytes(&self) -> usize {
+        match self.rom_size {
+            code @ 0x00..=0x08 => 0x8000 << code,
+            /* 只在少数非官方资料中出现过的 1.1/1.2/1.5MB */
+            0x52 => 72 * 0x4000,
+            0x53 => 80 * 0x4000,
+            0x54 => 96 * 0x4000,
+            _ => 0,
+        }
+    }
+
+    /* 16KB 一个 bank */
+    pub fn rom_banks(&self) -> usize {
+        self.rom_size_bytes() / 0x4000
+    }
+
+    pub fn ram_size_bytes(&self) -> usize {
+        match self.ram_size {
+            0x02 => 0x2000,
+            0x03 => 0x8000,
+            0x04 => 0x20000,
+            0x05 => 0x10000,
+            _ => 0,
+        }
+    }
+
+    /* 8KB 一个 bank */
+    pub fn ram_banks(&self) -> usize {
+        self.ram_size_bytes() / 0x2000
+    }
+
+    pub fn cgb_support(&self) -> CgbSupport {
+        match self.title[15] {
+            0xC0 => CgbSupport::Only,
+            flag if flag & 0x80 != 0 => CgbSupport::Enhanced,
+            _ => CgbSupport::None,
+        }
+    }
+
+    pub fn is_cgb(&self) -> bool {
+        self.cgb_support() != CgbSupport::None
+    }
+
+    /* SGB 只在 old licensee code 为 0x33 时才会启用 SGB 功能 */
+    pub fn is_sgb(&self) -> bool {
+        self.sgb_flag == 0x03 && self.old_licensee_code == 0x33
+    }
+
+    pub fn is_japanese(&self) -> bool {
+        self.dest_code == 0x00
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn is_checksum_match(&self) -> bool {
+        self.computed_checksum == self.checksum
+    }
+
+    /* 除 0x014E-0x014F 以外整个 ROM 的字节和 */
+    pub fn global_checksum(rom: &[u8]) -> u16 {
+        rom.iter()
+            .enumerate()
+            .filter(|(i, _)| !matches!(i, 0x14E | 0x14F))
+            .fold(0u16, |x, (_, byte)| x.wrapping_add(*byte as u16))
+    }
+
+    /* 实机 boot ROM 不检查 global checksum，只用于判断 ROM 是否完整 */
+    pub fn is_global_checksum_match(&self) -> bool {
+        self.computed_global_checksum == self.global_checksum
+    }
+
+    pub fn is_logo_match(&self) -> bool {
+        self.logo == NINTENDO_LOGO
+    }
+}
+
+impl std::fmt::Debug for RomHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RomHeader")
+            .field("title_str", &self.title_str())
+            .field("type", &format!("{:x?}", &self.cart_type))
+            .field("type_name", &self.cart_type_name())
+            .field("new_licensee_code", &self.new_licensee_code)
+            .field("old_licensee_code", &self.old_licensee_code)
+            .field("licensee_name", &self.licensee_name())
+            .field("rom_size", &self.rom_size_bytes())
+            .field("rom_banks", &self.rom_banks())
+            .field("ram_size", &self.ram_size_bytes())
+            .field("ram_banks", &self.ram_banks())
+            .field("cgb", &self.cgb_support())
+            .field("sgb", &self.is_sgb())
+            .field("checksum", &self.checksum)
+            .field("checksum_match", &self.is_checksum_match())
+            .field("global_checksum", &self.global_checksum)
+            .field("global_checksum_match", &self.is_global_checksum_match())
+            .field("logo_match", &self.is_logo_match())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_header() {
+        let mut data = vec![0; 0x150];
+        data[0x104..0x134].copy_from_slice(&NINTENDO_LOGO);
+        data[0x134..0x13F].copy_from_slice(b"POKEMON RED");
+        data[0x143] = 0x80;
+        data[0x144..0x146].copy_from_slice(b"01");
+        data[0x146] = 0x03;
+        data[0x147] = 0x13;
+        data[0x148] = 0x05;
+        data[0x149] = 0x03;
+        data[0x14A] = 0x01;
+        data[0x14B] = 0x33;
+        data[0x14C] = 0x02;
+        data[0x14D] = 0x9E;
+        data[0x14E..0x150].copy_from_slice(&[0x1A, 0x2D]);
+        let header = RomHeader::parse(&data).unwrap();
+        assert_eq!(header.title_str(), "POKEMON RED");
+        assert_eq!(header.cart_type_name(), "MBC3+RAM+BATTERY 10");
+        assert_eq!(header.licensee_name(), "Nintendo Research & Development 1");
+        assert_eq!(header.rom_size_bytes(), 0x100000);
+        assert_eq!(header.rom_banks(), 64);
+        assert_eq!(header.ram_size_bytes(), 0x8000);
+        assert_eq!(header.ram_banks(), 4);
+        assert_eq!(header.cgb_support(), CgbSupport::Enhanced);
+        assert!(header.is_cgb() && header.is_sgb() && !header.is_japanese());
+        assert_eq!(header.version(), 2);
+        assert!(header.is_checksum_match());
+        assert!(header.is_global_checksum_match());
+        assert!(header.is_logo_match());
+
+        /* 只有 old licensee code 为 0x33 时才使用 new licensee code，且才能启用 SGB */
+        data[0x14B] = 0x01;
+        data[0x143] = 0xC0;
+        data[0x104] = 0x00;
+        let header = RomHeader::parse(&data).unwrap();
+        assert_eq!(header.licensee_name(), "Nintendo");
+        assert!(!header.is_sgb());
+        assert_eq!(header.cgb_support(), CgbSupport::Only);
+        assert!(!header.is_checksum_match());
+        assert!(!header.is_global_checksum_match());
+        assert!(!header.is_logo_match());
+
+        assert_eq!(
+            RomHeader::parse(&data[..0x14F]).unwrap_err(),
+            CartridgeError::TooSmall(0x14F)
+        );
+    }
+}
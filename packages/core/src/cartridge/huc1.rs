@@ -0,0 +1,115 @@
+use super::{
+    infrared::{Disconnected, Infrared},
+    mapper::{Capabilities, Mapper},
+};
+
+pub struct HuC1 {
+    ram: Vec<u8>,
+    /* 0x0000-0x1FFF 写入 0x0E 时 0xA000-0xBFFF 切换为红外口 */
+    ir_mode: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+    infrared: Box<dyn Infrared>,
+}
+
+impl HuC1 {
+    pub fn new(ram_size: usize) -> Self {
+        HuC1 {
+            ram: vec![0; ram_size],
+            ir_mode: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            infrared: Box::new(Disconnected),
+        }
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        (self.ram_bank as usize * 0x2000 + (address as usize & 0x1FFF)) % self.ram.len()
+    }
+}
+
+impl Mapper for HuC1 {
+    fn read(&self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => rom[address as usize],
+            0x4000..=0x7FFF => {
+                let offset = self.rom_bank as usize * 0x4000 + (address as usize & 0x3FFF);
+                rom[offset % rom.len()]
+            }
+            0xA000..=0xBFFF if self.ir_mode => 0xC0 | self.infrared.is_receiving() as u8,
+            0xA000..=0xBFFF if !self.ram.is_empty() => self.ram[self.ram_offset(address)],
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) -> bool {
+        match address {
+            0x0000..=0x1FFF => self.ir_mode = value & 0x0F == 0x0E,
+            0x2000..=0x3FFF => {
+                self.rom_bank = match value & 0x3F {
+                    0 => 1,
+                    bank => bank,
+                }
+            }
+            0x4000..=0x5FFF => self.ram_bank = value & 0x03,
+            0xA000..=0xBFFF if self.ir_mode => self.infrared.set_led(value & 1 != 0),
+            0xA000..=0xBFFF if !self.ram.is_empty() => {
+                let offset = self.ram_offset(address);
+                self.ram[offset] = value;
+                return true;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            battery: true,
+            ..Default::default()
+        }
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let len = self.ram.len().min(data.len());
+        self.ram[..len].copy_from_slice(&data[..len]);
+    }
+
+    fn set_infrared(&mut self, infrared: Box<dyn Infrared>) {
+        self.infrared = infrared;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::infrared::tests::InfraredLink;
+
+    #[test]
+    fn ir_mode_switches_ram_window() {
+        let rom = vec![0; 0x8000];
+        let (a, b) = InfraredLink::pair();
+        let mut first = HuC1::new(0x8000);
+        let mut second = HuC1::new(0x8000);
+        first.set_infrared(Box::new(a));
+        second.set_infrared(Box::new(b));
+
+        first.write(0xA000, 0x42);
+        assert_eq!(first.read(&rom, 0xA000), 0x42);
+
+        first.write(0x0000, 0x0E);
+        second.write(0x0000, 0x0E);
+        assert_eq!(second.read(&rom, 0xA000), 0xC0);
+        first.write(0xA000, 0x01);
+        assert_eq!(second.read(&rom, 0xA000), 0xC1);
+        first.write(0xA000, 0x00);
+        assert_eq!(second.read(&rom, 0xA000), 0xC0);
+
+        first.write(0x0000, 0x0A);
+        assert_eq!(first.read(&rom, 0xA000), 0x42);
+    }
+}
@@ -0,0 +1,339 @@
+use super::{
+    clock::Clock,
+    infrared::{Disconnected, Infrared},
+    mapper::{Capabilities, Mapper},
+};
+
+/* 与 SameBoy 兼容的 RTC footer：u64 时间戳 + minutes/days/alarm_minutes/alarm_days (u16) + alarm_enabled (u8) */
+pub const RTC_FOOTER_SIZE: usize = 17;
+
+const MINUTES_PER_DAY: u16 = 60 * 24;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    RamReadOnly,
+    RamReadWrite,
+    Command,
+    Response,
+    Semaphore,
+    Infrared,
+    None,
+}
+
+struct Rtc {
+    clock: Box<dyn Clock>,
+    minutes: u16,
+    days: u16,
+    last_time: u64,
+}
+
+impl Rtc {
+    fn update(&mut self) {
+        let now = self.clock.now();
+        let elapsed = now.saturating_sub(self.last_time) / 60;
+        self.last_time += elapsed * 60;
+
+        let minutes = self.minutes as u64 + elapsed;
+        self.minutes = (minutes % MINUTES_PER_DAY as u64) as u16;
+        self.days = ((self.days as u64 + minutes / MINUTES_PER_DAY as u64) & 0xFFF) as u16;
+    }
+}
+
+pub struct HuC3 {
+    ram: Vec<u8>,
+    mode: Mode,
+    rom_bank: u8,
+    ram_bank: u8,
+    rtc: Rtc,
+    /* RTC 芯片内部 256 个 4bit 存储单元，0x00-0x05 为时间，0x10-0x16 为闹钟 */
+    memory: [u8; 0x100],
+    access_address: u8,
+    command: u8,
+    response: u8,
+    infrared: Box<dyn Infrared>,
+    tone: Option<u8>,
+    tone_callback: Option<Box<dyn FnMut(Option<u8>)>>,
+}
+
+impl HuC3 {
+    pub fn new(ram_size: usize, clock: Box<dyn Clock>) -> Self {
+        let last_time = clock.now();
+        HuC3 {
+            ram: vec![0; ram_size],
+            mode: Mode::None,
+            rom_bank: 1,
+            ram_bank: 0,
+            rtc: Rtc {
+                clock,
+                minutes: 0,
+                days: 0,
+                last_time,
+            },
+            memory: [0; 0x100],
+            access_address: 0,
+            command: 0,
+            response: 0,
+            infrared: Box::new(Disconnected),
+            tone: None,
+            tone_callback: None,
+        }
+    }
+
+    fn read_nibbles(&self, address: usize, count: usize) -> u16 {
+        (0..count).fold(0, |value, i| {
+            value | ((self.memory[address + i] & 0x0F) as u16) << (i * 4)
+        })
+    }
+
+    fn write_nibbles(&mut self, address: usize, count: usize, value: u16) {
+        for i in 0..count {
+            self.memory[address + i] = (value >> (i * 4)) as u8 & 0x0F;
+        }
+    }
+
+    fn execute(&mut self, command: u8, argument: u8) {
+        self.command = command;
+        match command {
+            /* 读取当前地址并自增 */
+            0x1 => {
+                self.response = self.memory[self.access_address as usize];
+                self.access_address = self.access_address.wrapping_add(1);
+            }
+            /* 写入当前地址并自增 */
+            0x3 => {
+                self.memory[self.access_address as usize] = argument;
+                self.access_address = self.access_address.wrapping_add(1);
+            }
+            0x4 => self.access_address = (self.access_address & 0xF0) | argument,
+            0x5 => self.access_address = (self.access_address & 0x0F) | argument << 4,
+            0x6 => match argument {
+                /* 把当前时间锁存到 0x00-0x05 */
+                0x0 => {
+                    self.rtc.update();
+                    self.write_nibbles(0x00, 3, self.rtc.minutes);
+                    self.write_nibbles(0x03, 3, self.rtc.days);
+                }
+                /* 用 0x00-0x05 设置时间 */
+                0x1 => {
+                    self.rtc.update();
+                    self.rtc.minutes = self.read_nibbles(0x00, 3) % MINUTES_PER_DAY;
+                    self.rtc.days = self.read_nibbles(0x03, 3);
+                }
+                /* 状态查询，游戏要求返回 1 */
+                0x2 => self.response = 0x1,
+                /* 蜂鸣器：0x27 为开关，0x26 为音调 */
+                0xE => {
+                    let tone = if self.memory[0x27] & 1 != 0 {
+                        Some(self.memory[0x26])
+                    } else {
+                        None
+                    };
+                    if tone != self.tone {
+                        self.tone = tone;
+                        if let Some(callback) = &mut self.tone_callback {
+                            callback(tone);
+                        }
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        (self.ram_bank as usize * 0x2000 + (address as usize & 0x1FFF)) % self.ram.len()
+    }
+}
+
+impl Mapper for HuC3 {
+    fn read(&self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => rom[address as usize],
+            0x4000..=0x7FFF => {
+                let offset = self.rom_bank as usize * 0x4000 + (address as usize & 0x3FFF);
+                rom[offset % rom.len()]
+            }
+            0xA000..=0xBFFF => match self.mode {
+                Mode::RamReadOnly | Mode::RamReadWrite if !self.ram.is_empty() => {
+                    self.ram[self.ram_offset(address)]
+                }
+                Mode::Response => 0x80 | self.command << 4 | self.response,
+                /* 命令总是立即完成 */
+                Mode::Semaphore => 0x01,
+                Mode::Infrared => 0xC0 | self.infrared.is_receiving() as u8,
+                _ => 0xFF,
+            },
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) -> bool {
+        match address {
+            0x0000..=0x1FFF => {
+                self.mode = match value & 0x0F {
+                    0x0 => Mode::RamReadOnly,
+                    0xA => Mode::RamReadWrite,
+                    0xB => Mode::Command,
+                    0xC => Mode::Response,
+                    0xD => Mode::Semaphore,
+                    0xE => Mode::Infrared,
+                    _ => Mode::None,
+                }
+            }
+            0x2000..=0x3FFF => {
+                self.rom_bank = match value & 0x7F {
+                    0 => 1,
+                    bank => bank,
+                }
+            }
+            0x4000..=0x5FFF => self.ram_bank = value & 0x03,
+            0xA000..=0xBFFF => match self.mode {
+                Mode::RamReadWrite if !self.ram.is_empty() => {
+                    let offset = self.ram_offset(address);
+                    self.ram[offset] = value;
+                    return true;
+                }
+                Mode::Command => self.execute((value >> 4) & 0x07, value & 0x0F),
+                Mode::Infrared => self.infrared.set_led(value & 1 != 0),
+                _ => {}
+            },
+            _ => {}
+        }
+        false
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            battery: true,
+            rtc: true,
+            ..Default::default()
+        }
+    }
+
+    #[cfg(test)]
+    fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.rtc.update();
+        self.rtc.last_time = clock.now();
+        self.rtc.clock = clock;
+    }
+
+    fn set_infrared(&mut self, infrared: Box<dyn Infrared>) {
+        self.infrared = infrared;
+    }
+
+    /**
+     * 蜂鸣器状态变化时回调，Some(tone) 表示开始以对应音调发声
+     */
+    fn set_tone_callback(&mut self, callback: Box<dyn FnMut(Option<u8>)>) {
+        self.tone_callback = Some(callback);
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        let mut data = self.ram.clone();
+        let elapsed = self.rtc.clock.now().saturating_sub(self.rtc.last_time) / 60;
+        let minutes = self.rtc.minutes as u64 + elapsed;
+        let days = (self.rtc.days as u64 + minutes / MINUTES_PER_DAY as u64) & 0xFFF;
+        data.extend_from_slice(&(self.rtc.last_time + elapsed * 60).to_le_bytes());
+        data.extend_from_slice(&((minutes % MINUTES_PER_DAY as u64) as u16).to_le_bytes());
+        data.extend_from_slice(&(days as u16).to_le_bytes());
+        data.extend_from_slice(&self.read_nibbles(0x10, 3).to_le_bytes());
+        data.extend_from_slice(&self.read_nibbles(0x13, 3).to_le_bytes());
+        data.push(self.memory[0x16] & 1);
+        data
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let len = self.ram.len().min(data.len());
+        self.ram[..len].copy_from_slice(&data[..len]);
+
+        let footer = &data[len..];
+        if footer.len() != RTC_FOOTER_SIZE {
+            return;
+        }
+        let u16_at = |offset: usize| u16::from_le_bytes([footer[offset], footer[offset + 1]]);
+        self.rtc.last_time = u64::from_le_bytes(footer[0..8].try_into().unwrap());
+        self.rtc.minutes = u16_at(8) % MINUTES_PER_DAY;
+        self.rtc.days = u16_at(10) & 0xFFF;
+        self.write_nibbles(0x10, 3, u16_at(12));
+        self.write_nibbles(0x13, 3, u16_at(14));
+        self.memory[0x16] = footer[16] & 1;
+        self.rtc.update();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::clock::tests::FakeClock;
+
+    fn command(mbc: &mut HuC3, command: u8, argument: u8) {
+        mbc.write(0x0000, 0x0B);
+        mbc.write(0xA000, command << 4 | argument);
+    }
+
+    fn read_nibble(mbc: &mut HuC3, rom: &[u8]) -> u8 {
+        command(mbc, 0x1, 0);
+        mbc.write(0x0000, 0x0C);
+        mbc.read(rom, 0xA000) & 0x0F
+    }
+
+    #[test]
+    fn rtc_latch_through_command_protocol() {
+        let rom = vec![0; 0x8000];
+        let clock = FakeClock::default();
+        let mut mbc = HuC3::new(0x2000, Box::new(clock.clone()));
+
+        clock.advance(2 * 86400 + 0x123 * 60 + 59);
+        command(&mut mbc, 0x6, 0x0);
+        command(&mut mbc, 0x4, 0x0);
+        command(&mut mbc, 0x5, 0x0);
+        let nibbles: Vec<u8> = (0..6).map(|_| read_nibble(&mut mbc, &rom)).collect();
+        assert_eq!(nibbles, vec![0x3, 0x2, 0x1, 0x2, 0x0, 0x0]);
+
+        mbc.write(0x0000, 0x0D);
+        assert_eq!(mbc.read(&rom, 0xA000) & 1, 1);
+    }
+
+    #[test]
+    fn rtc_set_and_save_footer() {
+        let clock = FakeClock::default();
+        clock.0.set(1_000_000);
+        let mut mbc = HuC3::new(0x2000, Box::new(clock.clone()));
+
+        command(&mut mbc, 0x4, 0x0);
+        command(&mut mbc, 0x5, 0x0);
+        for nibble in [0xF, 0x5, 0x0, 0x1, 0x0, 0x0] {
+            command(&mut mbc, 0x3, nibble);
+        }
+        command(&mut mbc, 0x6, 0x1);
+
+        let save = mbc.save_data();
+        assert_eq!(save.len(), 0x2000 + RTC_FOOTER_SIZE);
+        assert_eq!(&save[0x2008..0x200C], &[0x5F, 0x00, 0x01, 0x00]);
+
+        clock.advance(MINUTES_PER_DAY as u64 * 60);
+        let mut restored = HuC3::new(0x2000, Box::new(clock.clone()));
+        restored.load_save_data(&save);
+        assert_eq!((restored.rtc.minutes, restored.rtc.days), (0x5F, 2));
+    }
+
+    #[test]
+    fn tone_generator() {
+        let clock = FakeClock::default();
+        let mut mbc = HuC3::new(0, Box::new(clock));
+        let tones = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let recorder = std::rc::Rc::clone(&tones);
+        mbc.set_tone_callback(Box::new(move |tone| recorder.borrow_mut().push(tone)));
+
+        command(&mut mbc, 0x4, 0x6);
+        command(&mut mbc, 0x5, 0x2);
+        command(&mut mbc, 0x3, 0x3);
+        command(&mut mbc, 0x3, 0x1);
+        command(&mut mbc, 0x6, 0xE);
+        command(&mut mbc, 0x4, 0x7);
+        command(&mut mbc, 0x3, 0x0);
+        command(&mut mbc, 0x6, 0xE);
+        assert_eq!(*tones.borrow(), vec![Some(0x3), None]);
+    }
+}
@@ -0,0 +1,59 @@
+/**
+ * 卡带上的红外收发口，由宿主或另一个模拟器实例驱动
+ */
+pub trait Infrared {
+    /* 本机红外 LED 亮灭 */
+    fn set_led(&mut self, on: bool);
+    /* 是否接收到对方发出的红外光 */
+    fn is_receiving(&self) -> bool;
+}
+
+/* 未连接任何设备，永远收不到信号 */
+pub struct Disconnected;
+
+impl Infrared for Disconnected {
+    fn set_led(&mut self, _on: bool) {}
+
+    fn is_receiving(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::Infrared;
+    use std::{cell::Cell, rc::Rc};
+
+    /**
+     * 同一进程中两个卡带之间的红外连接，一端的 LED 就是另一端的接收信号。
+     * wasm 中每个模拟器实例各占一个 worker，实际使用 SharedArrayBuffer 连接
+     */
+    pub struct InfraredLink {
+        led: Rc<Cell<bool>>,
+        remote: Rc<Cell<bool>>,
+    }
+
+    impl InfraredLink {
+        pub fn pair() -> (InfraredLink, InfraredLink) {
+            let a = Rc::new(Cell::new(false));
+            let b = Rc::new(Cell::new(false));
+            (
+                InfraredLink {
+                    led: Rc::clone(&a),
+                    remote: Rc::clone(&b),
+                },
+                InfraredLink { led: b, remote: a },
+            )
+        }
+    }
+
+    impl Infrared for InfraredLink {
+        fn set_led(&mut self, on: bool) {
+            self.led.set(on);
+        }
+
+        fn is_receiving(&self) -> bool {
+            self.remote.get()
+        }
+    }
+}
@@ -0,0 +1,130 @@
+#[cfg(test)]
+use super::clock::Clock;
+use super::{camera::ImageSource, infrared::Infrared, CartridgeError};
+
+/**
+ * 卡带上除 ROM 以外的附加硬件
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Capabilities {
+    /* 外部 RAM / EEPROM 由电池供电，需要持久化 */
+    pub battery: bool,
+    pub rtc: bool,
+    pub rumble: bool,
+    /* 加速度计或摄像头等传感器 */
+    pub sensor: bool,
+}
+
+/**
+ * 要使用的 mapper，通常由 header 中的卡带类型决定，也可以由 GBX footer 指定
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MapperKind {
+    RomOnly,
+    Mbc1,
+    /* MBC1M 多合一卡带 */
+    Mbc1Multicart,
+    Mbc2,
+    Mbc3,
+    Mbc5,
+    Mbc7,
+    Mmm01,
+    Camera,
+    Tama5,
+    HuC1,
+    HuC3,
+    WisdomTree,
+    SachenMmc1,
+    SachenMmc2,
+    /* 盗版 MBC1 多合一卡带 */
+    Mbc1Bootleg,
+}
+
+impl std::str::FromStr for MapperKind {
+    type Err = CartridgeError;
+
+    /* 用于手动指定 mapper，名字不区分大小写 */
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Ok(match name.to_ascii_lowercase().as_str() {
+            "rom" => MapperKind::RomOnly,
+            "mbc1" => MapperKind::Mbc1,
+            "mbc1m" => MapperKind::Mbc1Multicart,
+            "mbc2" => MapperKind::Mbc2,
+            "mbc3" => MapperKind::Mbc3,
+            "mbc5" => MapperKind::Mbc5,
+            "mbc7" => MapperKind::Mbc7,
+            "mmm01" => MapperKind::Mmm01,
+            "camera" => MapperKind::Camera,
+            "tama5" => MapperKind::Tama5,
+            "huc1" => MapperKind::HuC1,
+            "huc3" => MapperKind::HuC3,
+            "wisdom-tree" => MapperKind::WisdomTree,
+            "sachen-mmc1" => MapperKind::SachenMmc1,
+            "sachen-mmc2" => MapperKind::SachenMmc2,
+            "mbc1-bootleg" => MapperKind::Mbc1Bootleg,
+            _ => return Err(CartridgeError::UnsupportedMapper(name.to_string())),
+        })
+    }
+}
+
+/**
+ * 创建 mapper 所需的信息，rtc / rumble 只对 MBC3 / MBC5 有意义
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapperConfig {
+    pub kind: MapperKind,
+    pub ram_size: usize,
+    pub battery: bool,
+    pub rtc: bool,
+    pub rumble: bool,
+}
+
+/**
+ * 卡带上的存储控制器，负责 ROM/RAM 的 bank 切换以及卡带上的附加硬件，
+ * 附加硬件相关的方法默认什么都不做
+ */
+pub trait Mapper {
+    fn read(&self, rom: &[u8], address: u16) -> u8;
+
+    /* 返回电池供电的数据（RAM / EEPROM）是否被写入 */
+    fn write(&mut self, address: u16, value: u8) -> bool;
+
+    fn capabilities(&self) -> Capabilities;
+
+    /* 需要持久化的数据，带 RTC 的卡带会在 RAM 之后附加 footer */
+    fn save_data(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    fn load_save_data(&mut self, _data: &[u8]) {}
+
+    /* 每个 M-cycle 调用一次 */
+    fn tick(&mut self) {}
+
+    /* 没有运行 boot ROM 时直接进入 boot ROM 结束后的状态 */
+    fn skip_boot(&mut self) {}
+
+    fn is_rumbling(&self) -> bool {
+        false
+    }
+
+    #[cfg(test)]
+    fn set_clock(&mut self, _clock: Box<dyn Clock>) {}
+
+    fn set_tilt_source(&mut self, _tilt: Box<dyn Fn() -> (f32, f32)>) {}
+
+    fn set_infrared(&mut self, _infrared: Box<dyn Infrared>) {}
+
+    fn set_tone_callback(&mut self, _callback: Box<dyn FnMut(Option<u8>)>) {}
+
+    /* image 为 128x120 的灰度传感器图像，返回卡带是否带有摄像头 */
+    fn set_camera_image(&mut self, _image: Vec<u8>) -> bool {
+        false
+    }
+
+    fn set_camera_source(&mut self, _source: ImageSource) {}
+
+    fn camera_capture(&self) -> Option<Vec<u8>> {
+        None
+    }
+}
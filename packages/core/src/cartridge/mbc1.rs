@@ -0,0 +1,173 @@
+use super::mapper::{Capabilities, Mapper};
+use crate::utils::bit;
+
+pub struct Mbc1 {
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    /* 0x2000-0x3FFF 写入的 5 位 ROM bank */
+    bank1: u8,
+    /* 0x4000-0x5FFF 写入的 2 位高位 ROM bank / RAM bank */
+    bank2: u8,
+    /* banking mode select, true 时 bank2 同时作用于 0x0000-0x3FFF 和 RAM */
+    advanced_mode: bool,
+    /* MBC1M: bank1 只有低 4 位接入，bank2 从第 4 位开始 */
+    multicart: bool,
+    battery: bool,
+}
+
+impl Mbc1 {
+    pub fn new(ram_size: usize, multicart: bool, battery: bool) -> Self {
+        Mbc1 {
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            bank1: 1,
+            bank2: 0,
+            advanced_mode: false,
+            multicart,
+            battery,
+        }
+    }
+
+    fn rom_offset(&self, address: u16) -> usize {
+        let (bank1, bank2) = if self.multicart {
+            (self.bank1 as usize & 0x0F, (self.bank2 as usize) << 4)
+        } else {
+            (self.bank1 as usize, (self.bank2 as usize) << 5)
+        };
+        let bank = match address {
+            0x0000..=0x3FFF if self.advanced_mode => bank2,
+            0x0000..=0x3FFF => 0,
+            _ => bank2 | bank1,
+        };
+        bank * 0x4000 + (address as usize & 0x3FFF)
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        let bank = if self.advanced_mode {
+            self.bank2 as usize
+        } else {
+            0
+        };
+        (bank * 0x2000 + (address as usize & 0x1FFF)) % self.ram.len()
+    }
+}
+
+impl Mapper for Mbc1 {
+    fn read(&self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            0x0000..=0x7FFF => rom[self.rom_offset(address) % rom.len()],
+            0xA000..=0xBFFF if self.ram_enabled && !self.ram.is_empty() => {
+                self.ram[self.ram_offset(address)]
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) -> bool {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                self.bank1 = match value & 0x1F {
+                    0 => 1,
+                    bank => bank,
+                }
+            }
+            0x4000..=0x5FFF => self.bank2 = value & 0x03,
+            0x6000..=0x7FFF => self.advanced_mode = bit!(value, 0),
+            0xA000..=0xBFFF if self.ram_enabled && !self.ram.is_empty() => {
+                let offset = self.ram_offset(address);
+                self.ram[offset] = value;
+                return true;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            battery: self.battery,
+            ..Default::default()
+        }
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let len = self.ram.len().min(data.len());
+        self.ram[..len].copy_from_slice(&data[..len]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn banked_rom(banks: usize) -> Vec<u8> {
+        (0..banks * 0x4000).map(|i| (i / 0x4000) as u8).collect()
+    }
+
+    #[test]
+    fn rom_banking() {
+        let rom = banked_rom(128);
+        let mut mbc = Mbc1::new(0, false, false);
+
+        assert_eq!(mbc.read(&rom, 0x4000), 1);
+        mbc.write(0x2000, 0x00);
+        assert_eq!(mbc.read(&rom, 0x4000), 1);
+        mbc.write(0x2000, 0x12);
+        assert_eq!(mbc.read(&rom, 0x4000), 0x12);
+        mbc.write(0x4000, 0x03);
+        assert_eq!(mbc.read(&rom, 0x4000), 0x72);
+        assert_eq!(mbc.read(&rom, 0x0000), 0x00);
+
+        mbc.write(0x6000, 0x01);
+        assert_eq!(mbc.read(&rom, 0x0000), 0x60);
+
+        // bank 0x20 不能直接选中，会变为 0x21
+        mbc.write(0x2000, 0x20);
+        mbc.write(0x4000, 0x01);
+        assert_eq!(mbc.read(&rom, 0x4000), 0x21);
+    }
+
+    #[test]
+    fn multicart_rom_banking() {
+        let rom = banked_rom(64);
+        let mut mbc = Mbc1::new(0, true, false);
+
+        mbc.write(0x2000, 0x12);
+        assert_eq!(mbc.read(&rom, 0x4000), 0x02);
+        mbc.write(0x4000, 0x01);
+        assert_eq!(mbc.read(&rom, 0x4000), 0x12);
+        assert_eq!(mbc.read(&rom, 0x0000), 0x00);
+
+        mbc.write(0x6000, 0x01);
+        mbc.write(0x4000, 0x03);
+        assert_eq!(mbc.read(&rom, 0x0000), 0x30);
+        assert_eq!(mbc.read(&rom, 0x4000), 0x32);
+    }
+
+    #[test]
+    fn ram_banking() {
+        let rom = banked_rom(4);
+        let mut mbc = Mbc1::new(0x8000, false, true);
+
+        mbc.write(0xA000, 0x12);
+        assert_eq!(mbc.read(&rom, 0xA000), 0xFF);
+
+        mbc.write(0x0000, 0x0A);
+        mbc.write(0xA000, 0x12);
+        mbc.write(0x6000, 0x01);
+        mbc.write(0x4000, 0x02);
+        mbc.write(0xA000, 0x34);
+        assert_eq!(mbc.read(&rom, 0xA000), 0x34);
+
+        mbc.write(0x6000, 0x00);
+        assert_eq!(mbc.read(&rom, 0xA000), 0x12);
+
+        mbc.write(0x0000, 0x00);
+        assert_eq!(mbc.read(&rom, 0xA000), 0xFF);
+    }
+}
@@ -0,0 +1,97 @@
+use super::mapper::{Capabilities, Mapper};
+use crate::utils::bit;
+
+pub struct Mbc2 {
+    /* 内置 512 x 4bit RAM，只保存低 4 位 */
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    rom_bank: u8,
+    battery: bool,
+}
+
+impl Mbc2 {
+    pub fn new(battery: bool) -> Self {
+        Mbc2 {
+            ram: vec![0; 0x200],
+            ram_enabled: false,
+            rom_bank: 1,
+            battery,
+        }
+    }
+}
+
+impl Mapper for Mbc2 {
+    fn read(&self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => rom[address as usize],
+            0x4000..=0x7FFF => {
+                let offset = self.rom_bank as usize * 0x4000 + (address as usize & 0x3FFF);
+                rom[offset % rom.len()]
+            }
+            0xA000..=0xBFFF if self.ram_enabled => self.ram[address as usize & 0x1FF] | 0xF0,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) -> bool {
+        match address {
+            /* 地址第 8 位区分 RAM enable 与 ROM bank 寄存器 */
+            0x0000..=0x3FFF if bit!(address, 8) => {
+                self.rom_bank = match value & 0x0F {
+                    0 => 1,
+                    bank => bank,
+                }
+            }
+            0x0000..=0x3FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0xA000..=0xBFFF if self.ram_enabled => {
+                self.ram[address as usize & 0x1FF] = value & 0x0F;
+                return true;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            battery: self.battery,
+            ..Default::default()
+        }
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        for (dst, src) in self.ram.iter_mut().zip(data) {
+            *dst = src & 0x0F;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rom_bank_and_ram_enable_by_address_bit8() {
+        let rom: Vec<u8> = (0..16 * 0x4000).map(|i| (i / 0x4000) as u8).collect();
+        let mut mbc = Mbc2::new(true);
+
+        mbc.write(0x2100, 0x05);
+        assert_eq!(mbc.read(&rom, 0x4000), 5);
+        mbc.write(0x0100, 0x00);
+        assert_eq!(mbc.read(&rom, 0x4000), 1);
+
+        mbc.write(0x2000, 0x0A);
+        assert_eq!(mbc.read(&rom, 0x4000), 1);
+        mbc.write(0xA000, 0x3C);
+        assert_eq!(mbc.read(&rom, 0xA000), 0xFC);
+        assert_eq!(mbc.read(&rom, 0xA200), 0xFC);
+        assert_eq!(mbc.read(&rom, 0xBE00), 0xFC);
+
+        mbc.write(0x0000, 0x00);
+        assert_eq!(mbc.read(&rom, 0xA000), 0xFF);
+    }
+}
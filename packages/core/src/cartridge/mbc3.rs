@@ -0,0 +1,379 @@
+use super::{
+    clock::Clock,
+    mapper::{Capabilities, Mapper},
+};
+use crate::utils::{bit, set_bit};
+
+/* VBA-M / BGB 在 .sav 末尾追加的 RTC 数据：10 个 u32 寄存器 + u64 时间戳 */
+pub const RTC_FOOTER_SIZE: usize = 48;
+const RTC_FOOTER_SIZE_LEGACY: usize = 44;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct RtcRegisters {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    days_low: u8,
+    /* bit 0: day 第 8 位, bit 6: halt, bit 7: day carry */
+    days_high: u8,
+}
+
+impl RtcRegisters {
+    fn days(&self) -> u16 {
+        (self.days_high as u16 & 1) << 8 | self.days_low as u16
+    }
+
+    fn set_days(&mut self, days: u16) {
+        self.days_low = days as u8;
+        set_bit!(self.days_high, 0, days & 0x100 != 0);
+    }
+
+    fn is_halted(&self) -> bool {
+        bit!(self.days_high, 6)
+    }
+
+    fn read(&self, register: u8) -> u8 {
+        match register {
+            0x08 => self.seconds,
+            0x09 => self.minutes,
+            0x0A => self.hours,
+            0x0B => self.days_low,
+            0x0C => self.days_high,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, register: u8, value: u8) {
+        match register {
+            0x08 => self.seconds = value & 0x3F,
+            0x09 => self.minutes = value & 0x3F,
+            0x0A => self.hours = value & 0x1F,
+            0x0B => self.days_low = value,
+            0x0C => self.days_high = value & 0xC1,
+            _ => {}
+        }
+    }
+
+    /* 寄存器可能被写成非法值（如 seconds = 62），此时按硬件行为只在对应位宽内回绕 */
+    fn tick(&mut self) {
+        self.seconds = (self.seconds + 1) & 0x3F;
+        if self.seconds != 60 {
+            return;
+        }
+        self.seconds = 0;
+        self.minutes = (self.minutes + 1) & 0x3F;
+        if self.minutes != 60 {
+            return;
+        }
+        self.minutes = 0;
+        self.hours = (self.hours + 1) & 0x1F;
+        if self.hours != 24 {
+            return;
+        }
+        self.hours = 0;
+        self.add_days(1);
+    }
+
+    fn add_days(&mut self, days: u64) {
+        let days = self.days() as u64 + days;
+        if days >= 512 {
+            set_bit!(self.days_high, 7, true);
+        }
+        self.set_days((days % 512) as u16);
+    }
+
+    fn advance(&mut self, mut seconds: u64) {
+        if self.is_halted() {
+            return;
+        }
+        while seconds > 0 && (self.seconds >= 60 || self.minutes >= 60 || self.hours >= 24) {
+            self.tick();
+            seconds -= 1;
+        }
+        let total =
+            self.seconds as u64 + self.minutes as u64 * 60 + self.hours as u64 * 3600 + seconds;
+        self.seconds = (total % 60) as u8;
+        self.minutes = (total / 60 % 60) as u8;
+        self.hours = (total / 3600 % 24) as u8;
+        if total >= 86400 {
+            self.add_days(total / 86400);
+        }
+    }
+
+    fn to_footer(self, buffer: &mut Vec<u8>) {
+        for value in [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.days_low,
+            self.days_high,
+        ] {
+            buffer.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+    }
+
+    fn from_footer(data: &[u8]) -> Self {
+        let mut registers = RtcRegisters::default();
+        for (i, chunk) in data.chunks_exact(4).take(5).enumerate() {
+            let value = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as u8;
+            registers.write(0x08 + i as u8, value);
+        }
+        registers
+    }
+}
+
+struct Rtc {
+    clock: Box<dyn Clock>,
+    current: RtcRegisters,
+    latched: RtcRegisters,
+    /* 上次同步 current 时的宿主时间 */
+    last_time: u64,
+}
+
+impl Rtc {
+    fn new(clock: Box<dyn Clock>) -> Self {
+        let last_time = clock.now();
+        Rtc {
+            clock,
+            current: RtcRegisters::default(),
+            latched: RtcRegisters::default(),
+            last_time,
+        }
+    }
+
+    fn update(&mut self) {
+        let now = self.clock.now();
+        self.current.advance(now.saturating_sub(self.last_time));
+        self.last_time = now;
+    }
+
+    fn snapshot(&self) -> (RtcRegisters, u64) {
+        let now = self.clock.now();
+        let mut current = self.current;
+        current.advance(now.saturating_sub(self.last_time));
+        (current, now)
+    }
+}
+
+pub struct Mbc3 {
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    rom_bank: u8,
+    /* 0x00-0x03 选择 RAM bank，0x08-0x0C 选择 RTC 寄存器 */
+    select: u8,
+    latch_armed: bool,
+    rtc: Option<Rtc>,
+    battery: bool,
+}
+
+impl Mbc3 {
+    pub fn new(ram_size: usize, clock: Option<Box<dyn Clock>>, battery: bool) -> Self {
+        Mbc3 {
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            rom_bank: 1,
+            select: 0,
+            latch_armed: false,
+            rtc: clock.map(Rtc::new),
+            battery,
+        }
+    }
+}
+
+impl Mapper for Mbc3 {
+    fn read(&self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => rom[address as usize],
+            0x4000..=0x7FFF => {
+                let offset = self.rom_bank as usize * 0x4000 + (address as usize & 0x3FFF);
+                rom[offset % rom.len()]
+            }
+            0xA000..=0xBFFF if self.ram_enabled => match self.select {
+                0x00..=0x07 if !self.ram.is_empty() => {
+                    let offset = self.select as usize * 0x2000 + (address as usize & 0x1FFF);
+                    self.ram[offset % self.ram.len()]
+                }
+                0x08..=0x0C => match &self.rtc {
+                    Some(rtc) => rtc.latched.read(self.select),
+                    None => 0xFF,
+                },
+                _ => 0xFF,
+            },
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) -> bool {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                self.rom_bank = match value & 0x7F {
+                    0 => 1,
+                    bank => bank,
+                }
+            }
+            0x4000..=0x5FFF => self.select = value & 0x0F,
+            0x6000..=0x7FFF => {
+                /* 先写 0 再写 1 时把当前时间锁存到 latched 寄存器 */
+                if self.latch_armed && value == 0x01 {
+                    if let Some(rtc) = &mut self.rtc {
+                        rtc.update();
+                        rtc.latched = rtc.current;
+                    }
+                }
+                self.latch_armed = value == 0x00;
+            }
+            0xA000..=0xBFFF if self.ram_enabled => match self.select {
+                0x00..=0x07 if !self.ram.is_empty() => {
+                    let offset = self.select as usize * 0x2000 + (address as usize & 0x1FFF);
+                    let len = self.ram.len();
+                    self.ram[offset % len] = value;
+                    return true;
+                }
+                0x08..=0x0C => {
+                    if let Some(rtc) = &mut self.rtc {
+                        rtc.update();
+                        rtc.current.write(self.select, value);
+                        rtc.latched.write(self.select, value);
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        false
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            battery: self.battery,
+            rtc: self.rtc.is_some(),
+            ..Default::default()
+        }
+    }
+
+    /**
+     * 导出 RAM，有 RTC 时在末尾追加 48 字节的 RTC footer
+     */
+    fn save_data(&self) -> Vec<u8> {
+        let mut data = self.ram.clone();
+        if let Some(rtc) = &self.rtc {
+            let (current, now) = rtc.snapshot();
+            current.to_footer(&mut data);
+            rtc.latched.to_footer(&mut data);
+            data.extend_from_slice(&now.to_le_bytes());
+        }
+        data
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let len = self.ram.len().min(data.len());
+        self.ram[..len].copy_from_slice(&data[..len]);
+
+        let footer = &data[len..];
+        if let Some(rtc) = &mut self.rtc {
+            let timestamp = match footer.len() {
+                RTC_FOOTER_SIZE => u64::from_le_bytes(footer[40..48].try_into().unwrap()),
+                RTC_FOOTER_SIZE_LEGACY => {
+                    u32::from_le_bytes(footer[40..44].try_into().unwrap()) as u64
+                }
+                _ => return,
+            };
+            rtc.current = RtcRegisters::from_footer(&footer[0..20]);
+            rtc.latched = RtcRegisters::from_footer(&footer[20..40]);
+            rtc.last_time = timestamp;
+            rtc.update();
+        }
+    }
+
+    #[cfg(test)]
+    fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        if let Some(rtc) = &mut self.rtc {
+            rtc.update();
+            rtc.last_time = clock.now();
+            rtc.clock = clock;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::clock::tests::FakeClock;
+
+    fn latch(mbc: &mut Mbc3) {
+        mbc.write(0x6000, 0x00);
+        mbc.write(0x6000, 0x01);
+    }
+
+    #[test]
+    fn rtc_latch_and_day_carry() {
+        let rom = vec![0; 0x8000];
+        let clock = FakeClock::default();
+        clock.0.set(1_000_000);
+        let mut mbc = Mbc3::new(0x2000, Some(Box::new(clock.clone())), true);
+        mbc.write(0x0000, 0x0A);
+
+        mbc.write(0x4000, 0x08);
+        clock.advance(61);
+        assert_eq!(mbc.read(&rom, 0xA000), 0);
+        latch(&mut mbc);
+        assert_eq!(mbc.read(&rom, 0xA000), 1);
+        mbc.write(0x4000, 0x09);
+        assert_eq!(mbc.read(&rom, 0xA000), 1);
+
+        clock.advance(511 * 86400 + 23 * 3600 + 58 * 60 + 59);
+        latch(&mut mbc);
+        mbc.write(0x4000, 0x0C);
+        assert_eq!(mbc.read(&rom, 0xA000), 0x80);
+        mbc.write(0x4000, 0x0B);
+        assert_eq!(mbc.read(&rom, 0xA000), 0x00);
+    }
+
+    #[test]
+    fn rtc_halt() {
+        let rom = vec![0; 0x8000];
+        let clock = FakeClock::default();
+        let mut mbc = Mbc3::new(0, Some(Box::new(clock.clone())), true);
+        mbc.write(0x0000, 0x0A);
+        mbc.write(0x4000, 0x0C);
+        mbc.write(0xA000, 0x40);
+        clock.advance(100);
+        mbc.write(0x4000, 0x08);
+        latch(&mut mbc);
+        assert_eq!(mbc.read(&rom, 0xA000), 0);
+
+        mbc.write(0x4000, 0x0C);
+        mbc.write(0xA000, 0x00);
+        clock.advance(5);
+        latch(&mut mbc);
+        mbc.write(0x4000, 0x08);
+        assert_eq!(mbc.read(&rom, 0xA000), 5);
+    }
+
+    #[test]
+    fn save_with_rtc_footer_keeps_running() {
+        let clock = FakeClock::default();
+        clock.0.set(1_000_000);
+        let mut mbc = Mbc3::new(0x2000, Some(Box::new(clock.clone())), true);
+        mbc.write(0x0000, 0x0A);
+        mbc.write(0xA000, 0x42);
+        mbc.write(0x4000, 0x0A);
+        mbc.write(0xA000, 5);
+
+        let save = mbc.save_data();
+        assert_eq!(save.len(), 0x2000 + RTC_FOOTER_SIZE);
+        assert_eq!(&save[0x2000 + 8..0x2000 + 12], &[5, 0, 0, 0]);
+        assert_eq!(&save[0x2000 + 40..], &1_000_000u64.to_le_bytes());
+
+        clock.advance(3600);
+        let rom = vec![0; 0x8000];
+        let mut restored = Mbc3::new(0x2000, Some(Box::new(clock.clone())), true);
+        restored.load_save_data(&save);
+        restored.write(0x0000, 0x0A);
+        assert_eq!(restored.read(&rom, 0xA000), 0x42);
+        latch(&mut restored);
+        restored.write(0x4000, 0x0A);
+        assert_eq!(restored.read(&rom, 0xA000), 6);
+    }
+}
@@ -0,0 +1,127 @@
+use super::mapper::{Capabilities, Mapper};
+use crate::utils::bit;
+
+pub struct Mbc5 {
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    /* 9 位 ROM bank，bank 0 也可以映射到 0x4000-0x7FFF */
+    rom_bank: u16,
+    ram_bank: u8,
+    /* rumble 卡带上 RAM bank 寄存器的第 3 位控制马达 */
+    rumble: Option<bool>,
+    battery: bool,
+}
+
+impl Mbc5 {
+    pub fn new(ram_size: usize, has_rumble: bool, battery: bool) -> Self {
+        Mbc5 {
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            rumble: if has_rumble { Some(false) } else { None },
+            battery,
+        }
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        (self.ram_bank as usize * 0x2000 + (address as usize & 0x1FFF)) % self.ram.len()
+    }
+}
+
+impl Mapper for Mbc5 {
+    fn read(&self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => rom[address as usize],
+            0x4000..=0x7FFF => {
+                let offset = self.rom_bank as usize * 0x4000 + (address as usize & 0x3FFF);
+                rom[offset % rom.len()]
+            }
+            0xA000..=0xBFFF if self.ram_enabled && !self.ram.is_empty() => {
+                self.ram[self.ram_offset(address)]
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) -> bool {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | value as u16,
+            0x3000..=0x3FFF => self.rom_bank = (self.rom_bank & 0xFF) | (value as u16 & 1) << 8,
+            0x4000..=0x5FFF => match &mut self.rumble {
+                Some(rumble) => {
+                    *rumble = bit!(value, 3);
+                    self.ram_bank = value & 0x07;
+                }
+                None => self.ram_bank = value & 0x0F,
+            },
+            0xA000..=0xBFFF if self.ram_enabled && !self.ram.is_empty() => {
+                let offset = self.ram_offset(address);
+                self.ram[offset] = value;
+                return true;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            battery: self.battery,
+            rumble: self.rumble.is_some(),
+            ..Default::default()
+        }
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let len = self.ram.len().min(data.len());
+        self.ram[..len].copy_from_slice(&data[..len]);
+    }
+
+    fn is_rumbling(&self) -> bool {
+        self.rumble.unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nine_bit_rom_banking() {
+        let rom: Vec<u8> = (0..512 * 0x4000)
+            .map(|i| (i / 0x4000) as u8 ^ ((i / 0x4000) >> 8) as u8)
+            .collect();
+        let mut mbc = Mbc5::new(0, false, false);
+
+        mbc.write(0x2000, 0x00);
+        assert_eq!(mbc.read(&rom, 0x4000), 0x00);
+        mbc.write(0x2000, 0x34);
+        mbc.write(0x3000, 0x01);
+        assert_eq!(mbc.read(&rom, 0x4000), 0x35);
+        mbc.write(0x2000, 0xFF);
+        assert_eq!(mbc.read(&rom, 0x7FFF), 0xFE);
+    }
+
+    #[test]
+    fn rumble_uses_ram_bank_bit3() {
+        let mut mbc = Mbc5::new(0x8000, true, true);
+        let rom = vec![0; 0x8000];
+        mbc.write(0x0000, 0x0A);
+        mbc.write(0x4000, 0x01);
+        mbc.write(0xA000, 0x11);
+        assert!(!mbc.is_rumbling());
+
+        mbc.write(0x4000, 0x09);
+        assert!(mbc.is_rumbling());
+        assert_eq!(mbc.read(&rom, 0xA000), 0x11);
+
+        mbc.write(0x4000, 0x01);
+        assert!(!mbc.is_rumbling());
+    }
+}
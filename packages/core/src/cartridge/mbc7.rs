@@ -0,0 +1,388 @@
+use super::mapper::{Capabilities, Mapper};
+use crate::utils::bit;
+
+/* 水平放置时加速度计的读数，约 0x70 对应 1g */
+const ACCELEROMETER_CENTER: f32 = 0x81D0 as f32;
+const ACCELEROMETER_SCALE: f32 = 0x70 as f32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EepromState {
+    /* 等待 start bit */
+    Idle,
+    /* 接收 2 位 opcode + 8 位地址 */
+    Command,
+    Read { address: u8, bit: u8 },
+    Write { address: Option<u8>, bit: u8 },
+    /* 命令执行完毕，等待 CS 拉低 */
+    Done,
+}
+
+/**
+ * 93LC56 / 93LC66 串行 EEPROM，分别按 128 / 256 x 16bit 组织
+ */
+struct Eeprom {
+    data: Vec<u16>,
+    write_enabled: bool,
+    cs: bool,
+    clk: bool,
+    di: bool,
+    dout: bool,
+    shift: u16,
+    state: EepromState,
+}
+
+impl Eeprom {
+    fn new(words: usize) -> Self {
+        Eeprom {
+            data: vec![0xFFFF; words],
+            write_enabled: false,
+            cs: false,
+            clk: false,
+            di: false,
+            dout: true,
+            shift: 0,
+            state: EepromState::Idle,
+        }
+    }
+
+    /* 93LC56 只使用地址的低 7 位 */
+    fn index(&self, address: u8) -> usize {
+        address as usize % self.data.len()
+    }
+
+    fn read(&self) -> u8 {
+        (self.cs as u8) << 7 | (self.clk as u8) << 6 | (self.di as u8) << 1 | self.dout as u8
+    }
+
+    /* 返回 EEPROM 的内容是否被写入 */
+    fn write(&mut self, value: u8) -> bool {
+        let cs = bit!(value, 7);
+        let clk = bit!(value, 6);
+        self.di = bit!(value, 1);
+
+        let mut written = false;
+        if !cs {
+            self.state = EepromState::Idle;
+            self.dout = true;
+        } else if clk && !self.clk {
+            written = self.clock_in();
+        }
+        self.cs = cs;
+        self.clk = clk;
+        written
+    }
+
+    fn clock_in(&mut self) -> bool {
+        let di = self.di as u16;
+        match self.state {
+            EepromState::Idle => {
+                if self.di {
+                    /* start bit 作为哨兵，移到第 10 位时说明 opcode 和地址已经收满 */
+                    self.state = EepromState::Command;
+                    self.shift = 1;
+                }
+            }
+            EepromState::Command => {
+                self.shift = self.shift << 1 | di;
+                if self.shift & 0x400 != 0 {
+                    return self.execute((self.shift >> 8) as u8 & 0x03, self.shift as u8);
+                }
+            }
+            EepromState::Read { address, bit } => {
+                let word = self.data[self.index(address)];
+                self.dout = word & (0x8000 >> bit) != 0;
+                /* 连续读取时地址自动递增 */
+                self.state = if bit == 15 {
+                    EepromState::Read {
+                        address: address.wrapping_add(1),
+                        bit: 0,
+                    }
+                } else {
+                    EepromState::Read {
+                        address,
+                        bit: bit + 1,
+                    }
+                };
+            }
+            EepromState::Write { address, bit } => {
+                self.shift = self.shift << 1 | di;
+                if bit < 15 {
+                    self.state = EepromState::Write {
+                        address,
+                        bit: bit + 1,
+                    };
+                    return false;
+                }
+                let written = self.write_enabled;
+                if written {
+                    match address {
+                        Some(address) => {
+                            let index = self.index(address);
+                            self.data[index] = self.shift;
+                        }
+                        None => self.data.fill(self.shift),
+                    }
+                }
+                self.dout = true;
+                self.state = EepromState::Done;
+                return written;
+            }
+            EepromState::Done => {}
+        }
+        false
+    }
+
+    fn execute(&mut self, opcode: u8, address: u8) -> bool {
+        self.shift = 0;
+        let mut written = false;
+        self.state = match opcode {
+            0b10 => {
+                /* READ 先输出一个 dummy 0 */
+                self.dout = false;
+                EepromState::Read { address, bit: 0 }
+            }
+            0b01 => EepromState::Write {
+                address: Some(address),
+                bit: 0,
+            },
+            0b11 => {
+                if self.write_enabled {
+                    let index = self.index(address);
+                    self.data[index] = 0xFFFF;
+                    written = true;
+                }
+                EepromState::Done
+            }
+            _ => match address >> 6 {
+                0b11 => {
+                    self.write_enabled = true;
+                    EepromState::Done
+                }
+                0b00 => {
+                    self.write_enabled = false;
+                    EepromState::Done
+                }
+                0b10 => {
+                    if self.write_enabled {
+                        self.data.fill(0xFFFF);
+                        written = true;
+                    }
+                    EepromState::Done
+                }
+                _ => EepromState::Write {
+                    address: None,
+                    bit: 0,
+                },
+            },
+        };
+        written
+    }
+}
+
+pub struct Mbc7 {
+    rom_bank: u8,
+    ram_enabled1: bool,
+    ram_enabled2: bool,
+    eeprom: Eeprom,
+    battery: bool,
+    accelerometer: (u16, u16),
+    latch_ready: bool,
+    tilt: Box<dyn Fn() -> (f32, f32)>,
+}
+
+impl Mbc7 {
+    /* ram_size 为 512 时使用 93LC66，否则为 93LC56 */
+    pub fn new(ram_size: usize, battery: bool) -> Self {
+        Mbc7 {
+            rom_bank: 1,
+            ram_enabled1: false,
+            ram_enabled2: false,
+            eeprom: Eeprom::new(if ram_size >= 512 { 256 } else { 128 }),
+            battery,
+            accelerometer: (0x8000, 0x8000),
+            latch_ready: false,
+            tilt: Box::new(|| (0.0, 0.0)),
+        }
+    }
+
+    fn registers_enabled(&self) -> bool {
+        self.ram_enabled1 && self.ram_enabled2
+    }
+}
+
+impl Mapper for Mbc7 {
+    fn read(&self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => rom[address as usize],
+            0x4000..=0x7FFF => {
+                let offset = self.rom_bank as usize * 0x4000 + (address as usize & 0x3FFF);
+                rom[offset % rom.len()]
+            }
+            0xA000..=0xAFFF if self.registers_enabled() => match (address >> 4) & 0x0F {
+                0x2 => self.accelerometer.0 as u8,
+                0x3 => (self.accelerometer.0 >> 8) as u8,
+                0x4 => self.accelerometer.1 as u8,
+                0x5 => (self.accelerometer.1 >> 8) as u8,
+                0x6 => 0x00,
+                0x8 => self.eeprom.read(),
+                _ => 0xFF,
+            },
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) -> bool {
+        match address {
+            0x0000..=0x1FFF => {
+                self.ram_enabled1 = value == 0x0A;
+                if !self.ram_enabled1 {
+                    self.ram_enabled2 = false;
+                }
+            }
+            0x2000..=0x3FFF => self.rom_bank = value & 0x7F,
+            0x4000..=0x5FFF => self.ram_enabled2 = self.ram_enabled1 && value == 0x40,
+            0xA000..=0xAFFF if self.registers_enabled() => match (address >> 4) & 0x0F {
+                0x0 if value == 0x55 => {
+                    self.accelerometer = (0x8000, 0x8000);
+                    self.latch_ready = true;
+                }
+                0x1 if value == 0xAA && self.latch_ready => {
+                    let (x, y) = (self.tilt)();
+                    let axis = |g: f32| {
+                        (ACCELEROMETER_CENTER + g * ACCELEROMETER_SCALE).clamp(0.0, 65535.0) as u16
+                    };
+                    self.accelerometer = (axis(x), axis(y));
+                    self.latch_ready = false;
+                }
+                0x8 => return self.eeprom.write(value),
+                _ => {}
+            },
+            _ => {}
+        }
+        false
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            battery: self.battery,
+            sensor: true,
+            ..Default::default()
+        }
+    }
+
+    /**
+     * 设置倾斜传感器的数据来源，返回 X/Y 方向的加速度，单位为 g
+     */
+    fn set_tilt_source(&mut self, tilt: Box<dyn Fn() -> (f32, f32)>) {
+        self.tilt = tilt;
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.eeprom
+            .data
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        for (word, bytes) in self.eeprom.data.iter_mut().zip(data.chunks_exact(2)) {
+            *word = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enable(mbc: &mut Mbc7) {
+        mbc.write(0x0000, 0x0A);
+        mbc.write(0x4000, 0x40);
+    }
+
+    fn send_bits(mbc: &mut Mbc7, value: u32, bits: u8) {
+        for i in (0..bits).rev() {
+            let di = ((value >> i) & 1) as u8;
+            mbc.write(0xA080, 0x80 | di << 1);
+            mbc.write(0xA080, 0xC0 | di << 1);
+        }
+    }
+
+    fn read_word(mbc: &mut Mbc7, rom: &[u8], address: u8) -> u16 {
+        mbc.write(0xA080, 0x00);
+        send_bits(mbc, 0b110 << 8 | address as u32, 11);
+        assert_eq!(mbc.read(rom, 0xA080) & 1, 0);
+        let mut word = 0;
+        for _ in 0..16 {
+            mbc.write(0xA080, 0x80);
+            mbc.write(0xA080, 0xC0);
+            word = word << 1 | (mbc.read(rom, 0xA080) & 1) as u16;
+        }
+        mbc.write(0xA080, 0x00);
+        word
+    }
+
+    #[test]
+    fn accelerometer_latch() {
+        let rom = vec![0; 0x8000];
+        let mut mbc = Mbc7::new(0, true);
+        mbc.set_tilt_source(Box::new(|| (1.0, -0.5)));
+        enable(&mut mbc);
+
+        mbc.write(0xA010, 0xAA);
+        assert_eq!(mbc.read(&rom, 0xA020), 0x00);
+        assert_eq!(mbc.read(&rom, 0xA030), 0x80);
+
+        mbc.write(0xA000, 0x55);
+        mbc.write(0xA010, 0xAA);
+        assert_eq!(mbc.read(&rom, 0xA020), 0x40);
+        assert_eq!(mbc.read(&rom, 0xA030), 0x82);
+        assert_eq!(mbc.read(&rom, 0xA040), 0x98);
+        assert_eq!(mbc.read(&rom, 0xA050), 0x81);
+    }
+
+    #[test]
+    fn eeprom_write_and_read() {
+        let rom = vec![0; 0x8000];
+        let mut mbc = Mbc7::new(0, true);
+        enable(&mut mbc);
+
+        // WRITE 在 EWEN 之前被忽略
+        mbc.write(0xA080, 0x00);
+        send_bits(&mut mbc, 0b101 << 8 | 0x05, 11);
+        send_bits(&mut mbc, 0x1234, 16);
+        assert_eq!(read_word(&mut mbc, &rom, 0x05), 0xFFFF);
+
+        mbc.write(0xA080, 0x00);
+        send_bits(&mut mbc, 0b100 << 8 | 0xC0, 11);
+        mbc.write(0xA080, 0x00);
+        send_bits(&mut mbc, 0b101 << 8 | 0x05, 11);
+        send_bits(&mut mbc, 0x1234, 16);
+        assert_eq!(read_word(&mut mbc, &rom, 0x05), 0x1234);
+        assert_eq!(&mbc.save_data()[10..12], &[0x34, 0x12]);
+
+        mbc.write(0xA080, 0x00);
+        send_bits(&mut mbc, 0b111 << 8 | 0x05, 11);
+        assert_eq!(read_word(&mut mbc, &rom, 0x05), 0xFFFF);
+    }
+
+    #[test]
+    fn eeprom_size_from_config() {
+        let rom = vec![0; 0x8000];
+        let mut mbc = Mbc7::new(512, true);
+        enable(&mut mbc);
+        assert_eq!(mbc.save_data().len(), 512);
+
+        mbc.write(0xA080, 0x00);
+        send_bits(&mut mbc, 0b100 << 8 | 0xC0, 11);
+        mbc.write(0xA080, 0x00);
+        send_bits(&mut mbc, 0b101 << 8 | 0x85, 11);
+        send_bits(&mut mbc, 0x1234, 16);
+        assert_eq!(read_word(&mut mbc, &rom, 0x85), 0x1234);
+        assert_eq!(read_word(&mut mbc, &rom, 0x05), 0xFFFF);
+
+        assert_eq!(Mbc7::new(0, true).save_data().len(), 256);
+        assert!(!Mbc7::new(0, false).capabilities().battery);
+    }
+}
@@ -0,0 +1,239 @@
+use super::mapper::{Capabilities, Mapper};
+use crate::utils::bit;
+
+/**
+ * MMM01 多合一卡带。上电时处于 unmapped 模式，映射 ROM 最后 32KB 中的菜单；
+ * 菜单设置好所选游戏的 bank 范围后写入 map enable，之后除了未被屏蔽的位外都被锁定，
+ * 游戏看到的就是一个普通的 MBC1
+ */
+pub struct Mmm01 {
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    mapped: bool,
+    rom_bank_low: u8,
+    rom_bank_mid: u8,
+    rom_bank_high: u8,
+    /* 为 1 的位在 mapped 模式下不再允许游戏修改（对应 rom_bank_low 的 bit 1-4） */
+    rom_bank_mask: u8,
+    ram_bank_low: u8,
+    ram_bank_high: u8,
+    ram_bank_mask: u8,
+    mbc1_mode: bool,
+    mbc1_mode_disabled: bool,
+    /* 复用 MBC1 的 RAM bank 寄存器作为 ROM bank 的 bit 5-6 */
+    multiplex: bool,
+    battery: bool,
+}
+
+impl Mmm01 {
+    pub fn new(ram_size: usize, battery: bool) -> Self {
+        Mmm01 {
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            mapped: false,
+            rom_bank_low: 0,
+            rom_bank_mid: 0,
+            rom_bank_high: 0,
+            rom_bank_mask: 0,
+            ram_bank_low: 0,
+            ram_bank_high: 0,
+            ram_bank_mask: 0,
+            mbc1_mode: false,
+            mbc1_mode_disabled: false,
+            multiplex: false,
+            battery,
+        }
+    }
+
+    /* 返回 (0x0000-0x3FFF 的 bank, 0x4000-0x7FFF 的 bank) */
+    fn rom_banks(&self) -> (usize, usize) {
+        if !self.mapped {
+            return (0x1FE, 0x1FF);
+        }
+        let mask = self.rom_bank_mask << 1;
+        let (mid0, mid) = if self.multiplex {
+            let mid = self.ram_bank_low as usize;
+            (if self.mbc1_mode { 0 } else { mid }, mid)
+        } else {
+            (self.rom_bank_mid as usize, self.rom_bank_mid as usize)
+        };
+        let high = (self.rom_bank_high as usize) << 7;
+        /* 与 MBC1 一样，游戏可写的 bank 位全为 0 时视为 1 */
+        let low = if self.rom_bank_low & !mask & 0x1F == 0 {
+            self.rom_bank_low | 1
+        } else {
+            self.rom_bank_low
+        };
+        let bank0 = (self.rom_bank_low & mask) as usize | mid0 << 5 | high;
+        let bank = low as usize | mid << 5 | high;
+        (bank0, bank)
+    }
+
+    fn ram_bank(&self) -> usize {
+        if !self.mapped {
+            return 0;
+        }
+        let low = if self.multiplex {
+            self.rom_bank_mid
+        } else {
+            self.ram_bank_low
+        };
+        (low | self.ram_bank_high << 2) as usize
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        (self.ram_bank() * 0x2000 + (address as usize & 0x1FFF)) % self.ram.len()
+    }
+}
+
+impl Mapper for Mmm01 {
+    fn read(&self, rom: &[u8], address: u16) -> u8 {
+        let (bank0, bank) = self.rom_banks();
+        match address {
+            0x0000..=0x3FFF => rom[(bank0 * 0x4000 + address as usize) % rom.len()],
+            0x4000..=0x7FFF => rom[(bank * 0x4000 + (address as usize & 0x3FFF)) % rom.len()],
+            0xA000..=0xBFFF if self.ram_enabled && !self.ram.is_empty() => {
+                self.ram[self.ram_offset(address)]
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) -> bool {
+        match address {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = value & 0x0F == 0x0A;
+                if !self.mapped {
+                    self.ram_bank_mask = (value >> 4) & 0x03;
+                    self.mapped = bit!(value, 6);
+                }
+            }
+            0x2000..=0x3FFF => {
+                if !self.mapped {
+                    self.rom_bank_mid = (value >> 5) & 0x03;
+                }
+                let mask = self.rom_bank_mask << 1;
+                self.rom_bank_low = (self.rom_bank_low & mask) | (value & !mask & 0x1F);
+            }
+            0x4000..=0x5FFF => {
+                let mask = self.ram_bank_mask;
+                self.ram_bank_low = (self.ram_bank_low & mask) | (value & !mask & 0x03);
+                if !self.mapped {
+                    self.ram_bank_high = (value >> 2) & 0x03;
+                    self.rom_bank_high = (value >> 4) & 0x03;
+                    self.mbc1_mode_disabled = bit!(value, 6);
+                }
+            }
+            0x6000..=0x7FFF => {
+                if !self.mbc1_mode_disabled {
+                    self.mbc1_mode = bit!(value, 0);
+                }
+                if !self.mapped {
+                    self.rom_bank_mask = (value >> 2) & 0x0F;
+                    self.multiplex = bit!(value, 6);
+                }
+            }
+            0xA000..=0xBFFF if self.ram_enabled && !self.ram.is_empty() => {
+                let offset = self.ram_offset(address);
+                self.ram[offset] = value;
+                return true;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            battery: self.battery,
+            ..Default::default()
+        }
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let len = self.ram.len().min(data.len());
+        self.ram[..len].copy_from_slice(&data[..len]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn menu_boots_from_last_banks() {
+        let rom: Vec<u8> = (0..0x40).flat_map(|bank| vec![bank; 0x4000]).collect();
+        let mut mbc = Mmm01::new(0, false);
+        assert_eq!(mbc.read(&rom, 0x0000), 0x3E);
+        assert_eq!(mbc.read(&rom, 0x4000), 0x3F);
+
+        /* bank 切换在 unmapped 模式下不影响菜单 */
+        mbc.write(0x2000, 0x05);
+        assert_eq!(mbc.read(&rom, 0x4000), 0x3F);
+    }
+
+    #[test]
+    fn mapped_game_is_confined_by_mask() {
+        let rom: Vec<u8> = (0..0x40).flat_map(|bank| vec![bank; 0x4000]).collect();
+        let mut mbc = Mmm01::new(0, false);
+
+        /* 选择从 bank 0x28 开始、共 8 个 bank 的游戏：bit 3-4 被锁定 */
+        mbc.write(0x2000, 0x28);
+        mbc.write(0x6000, 0b1100 << 2);
+        mbc.write(0x0000, 0x40);
+        assert_eq!(mbc.read(&rom, 0x0000), 0x28);
+        assert_eq!(mbc.read(&rom, 0x4000), 0x29);
+
+        mbc.write(0x2000, 0x03);
+        assert_eq!(mbc.read(&rom, 0x4000), 0x2B);
+        mbc.write(0x2000, 0x1F);
+        assert_eq!(mbc.read(&rom, 0x4000), 0x2F);
+        mbc.write(0x2000, 0x00);
+        assert_eq!(mbc.read(&rom, 0x4000), 0x29);
+
+        /* mapped 之后 mask 与 map enable 都被锁定 */
+        mbc.write(0x6000, 0x00);
+        mbc.write(0x0000, 0x00);
+        mbc.write(0x2000, 0x02);
+        assert_eq!(mbc.read(&rom, 0x4000), 0x2A);
+    }
+
+    #[test]
+    fn mapped_ram_bank_is_confined_by_mask() {
+        let rom = vec![0; 0x8000];
+        let mut mbc = Mmm01::new(0x8000, false);
+
+        /* 菜单选择 RAM bank 2，并锁定 bit 1 */
+        mbc.write(0x4000, 0x02);
+        mbc.write(0x0000, 0x6A);
+        mbc.write(0xA000, 0x22);
+        mbc.write(0x4000, 0x01);
+        mbc.write(0xA000, 0x33);
+
+        mbc.write(0x4000, 0x00);
+        assert_eq!(mbc.read(&rom, 0xA000), 0x22);
+        mbc.write(0x4000, 0x03);
+        assert_eq!(mbc.read(&rom, 0xA000), 0x33);
+        assert_eq!(mbc.ram[0x4000], 0x22);
+        assert_eq!(mbc.ram[0x6000], 0x33);
+    }
+
+    #[test]
+    fn zero_bank_remap_applies_before_mask() {
+        let rom: Vec<u8> = (0..0x80).flat_map(|bank| vec![bank; 0x4000]).collect();
+        let mut mbc = Mmm01::new(0, false);
+
+        /* 游戏从 bank 0x20 开始，未锁定任何位：写入 0 时映射 bank 0x21 */
+        mbc.write(0x2000, 0x20);
+        mbc.write(0x0000, 0x40);
+        mbc.write(0x2000, 0x00);
+        assert_eq!(mbc.read(&rom, 0x0000), 0x20);
+        assert_eq!(mbc.read(&rom, 0x4000), 0x21);
+        mbc.write(0x2000, 0x02);
+        assert_eq!(mbc.read(&rom, 0x4000), 0x22);
+    }
+}
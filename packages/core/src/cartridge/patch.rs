@@ -0,0 +1,457 @@
+use crate::utils::crc32;
+
+/* MBC5 最多寻址 8MB ROM，patch 声明的目标大小不能超过它 */
+const MAX_TARGET_SIZE: usize = 0x800000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchError {
+    /* 不是 IPS / UPS / BPS 中的任何一种 */
+    UnknownFormat,
+    /* patch 被截断或记录超出范围 */
+    Malformed(&'static str),
+    /* patch 对应的原始 ROM 与当前 ROM 不一致 */
+    SourceMismatch { expected: u32, actual: u32 },
+    TargetMismatch { expected: u32, actual: u32 },
+    PatchMismatch { expected: u32, actual: u32 },
+}
+
+impl std::fmt::Display for PatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatchError::UnknownFormat => write!(f, "unknown patch format"),
+            PatchError::Malformed(reason) => write!(f, "malformed patch: {}", reason),
+            PatchError::SourceMismatch { expected, actual } => write!(
+                f,
+                "patch is for a different ROM (expected CRC32 {:08X}, got {:08X})",
+                expected, actual
+            ),
+            PatchError::TargetMismatch { expected, actual } => write!(
+                f,
+                "patched ROM checksum mismatch (expected CRC32 {:08X}, got {:08X})",
+                expected, actual
+            ),
+            PatchError::PatchMismatch { expected, actual } => write!(
+                f,
+                "patch file is corrupted (expected CRC32 {:08X}, got {:08X})",
+                expected, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/**
+ * 按 magic 判断 patch 格式并应用到 rom 上，返回新的 ROM
+ */
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if patch.starts_with(b"PATCH") {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(b"UPS1") {
+        apply_ups(rom, patch)
+    } else if patch.starts_with(b"BPS1") {
+        apply_bps(rom, patch)
+    } else {
+        Err(PatchError::UnknownFormat)
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Self {
+        Reader { data, pos }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], PatchError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .ok_or(PatchError::Malformed("unexpected end of patch"))?;
+        let bytes = self
+            .data
+            .get(self.pos..end)
+            .ok_or(PatchError::Malformed("unexpected end of patch"))?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, PatchError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn be(&mut self, len: usize) -> Result<usize, PatchError> {
+        Ok(self
+            .bytes(len)?
+            .iter()
+            .fold(0, |value, &byte| value << 8 | byte as usize))
+    }
+
+    /* UPS / BPS 使用的变长整数，每个字节 7 位，最高位为 1 表示结束 */
+    fn varint(&mut self) -> Result<usize, PatchError> {
+        let mut value: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let byte = self.byte()?;
+            value = (byte as usize & 0x7F)
+                .checked_mul(shift)
+                .and_then(|x| x.checked_add(value))
+                .ok_or(PatchError::Malformed("number too large"))?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift
+                .checked_shl(7)
+                .ok_or(PatchError::Malformed("number too large"))?;
+            value = value
+                .checked_add(shift)
+                .ok_or(PatchError::Malformed("number too large"))?;
+        }
+    }
+}
+
+/**
+ * IPS：24 位偏移 + 16 位长度的记录，长度为 0 时是 RLE 记录，
+ * "EOF" 之后可选的 24 位长度用于截断 ROM
+ */
+fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let mut output = rom.to_vec();
+    let mut reader = Reader::new(patch, 5);
+    loop {
+        if reader.bytes(3)? == b"EOF" {
+            break;
+        }
+        reader.pos -= 3;
+        let offset = reader.be(3)?;
+        let size = reader.be(2)?;
+        let (size, value) = match size {
+            0 => (reader.be(2)?, Some(reader.byte()?)),
+            size => (size, None),
+        };
+        if output.len() < offset + size {
+            output.resize(offset + size, 0);
+        }
+        match value {
+            Some(value) => output[offset..offset + size].fill(value),
+            None => output[offset..offset + size].copy_from_slice(reader.bytes(size)?),
+        }
+    }
+    if let Ok(size) = reader.be(3) {
+        output.truncate(size);
+    }
+    Ok(output)
+}
+
+/* UPS / BPS 末尾的 source、target、patch 三个 CRC32 */
+fn check_footer(rom: &[u8], patch: &[u8]) -> Result<(u32, usize), PatchError> {
+    if patch.len() < 4 + 12 {
+        return Err(PatchError::Malformed("unexpected end of patch"));
+    }
+    let footer = patch.len() - 12;
+    let crc = |offset: usize| u32::from_le_bytes(patch[offset..offset + 4].try_into().unwrap());
+
+    let actual = crc32(&patch[..footer + 8]);
+    if crc(footer + 8) != actual {
+        return Err(PatchError::PatchMismatch {
+            expected: crc(footer + 8),
+            actual,
+        });
+    }
+    let actual = crc32(rom);
+    if crc(footer) != actual {
+        return Err(PatchError::SourceMismatch {
+            expected: crc(footer),
+            actual,
+        });
+    }
+    Ok((crc(footer + 4), footer))
+}
+
+/* UPS / BPS 开头声明的 source 与 target 大小 */
+fn read_sizes(rom: &[u8], reader: &mut Reader) -> Result<usize, PatchError> {
+    if reader.varint()? != rom.len() {
+        return Err(PatchError::Malformed("source size mismatch"));
+    }
+    let target_size = reader.varint()?;
+    if target_size > MAX_TARGET_SIZE {
+        return Err(PatchError::Malformed("target too large"));
+    }
+    Ok(target_size)
+}
+
+fn check_target(output: Vec<u8>, expected: u32) -> Result<Vec<u8>, PatchError> {
+    let actual = crc32(&output);
+    match actual == expected {
+        true => Ok(output),
+        false => Err(PatchError::TargetMismatch { expected, actual }),
+    }
+}
+
+/**
+ * UPS：每个 hunk 为相对偏移 + 与原 ROM 异或的字节，以 0 结尾
+ */
+fn apply_ups(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let (target_crc, footer) = check_footer(rom, patch)?;
+    let mut reader = Reader::new(&patch[..footer], 4);
+    let target_size = read_sizes(rom, &mut reader)?;
+
+    let out_of_range = PatchError::Malformed("hunk out of range");
+    let mut output = rom.to_vec();
+    output.resize(target_size, 0);
+    let mut pos: usize = 0;
+    while reader.pos < footer {
+        pos = pos
+            .checked_add(reader.varint()?)
+            .ok_or(out_of_range.clone())?;
+        loop {
+            let byte = reader.byte()?;
+            if byte == 0 {
+                break;
+            }
+            if let Some(target) = output.get_mut(pos) {
+                *target ^= byte;
+            }
+            pos = pos.checked_add(1).ok_or(out_of_range.clone())?;
+        }
+        pos = pos.checked_add(1).ok_or(out_of_range.clone())?;
+    }
+    check_target(output, target_crc)
+}
+
+/**
+ * BPS：由 SourceRead / TargetRead / SourceCopy / TargetCopy 四种命令组成
+ */
+fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let (target_crc, footer) = check_footer(rom, patch)?;
+    let mut reader = Reader::new(&patch[..footer], 4);
+    let target_size = read_sizes(rom, &mut reader)?;
+    let metadata_size = reader.varint()?;
+    reader.bytes(metadata_size)?;
+
+    let out_of_range = PatchError::Malformed("copy out of range");
+    let mut output = Vec::with_capacity(target_size);
+    let mut source_offset: usize = 0;
+    let mut target_offset: usize = 0;
+    while reader.pos < footer {
+        let data = reader.varint()?;
+        let length = (data >> 2) + 1;
+        match data & 3 {
+            0 => {
+                let start = output.len();
+                let bytes = rom.get(start..start + length).ok_or(out_of_range.clone())?;
+                output.extend_from_slice(bytes);
+            }
+            1 => output.extend_from_slice(reader.bytes(length)?),
+            command => {
+                let data = reader.varint()?;
+                let offset = if command == 2 {
+                    &mut source_offset
+                } else {
+                    &mut target_offset
+                };
+                *offset = match data & 1 {
+                    0 => offset.checked_add(data >> 1),
+                    _ => offset.checked_sub(data >> 1),
+                }
+                .ok_or(out_of_range.clone())?;
+                for _ in 0..length {
+                    /* TargetCopy 可能与正在写入的区域重叠，需要逐字节复制 */
+                    let byte = match command {
+                        2 => rom.get(*offset),
+                        _ => output.get(*offset),
+                    }
+                    .copied()
+                    .ok_or(out_of_range.clone())?;
+                    output.push(byte);
+                    *offset += 1;
+                }
+            }
+        }
+    }
+    if output.len() != target_size {
+        return Err(PatchError::Malformed("target size mismatch"));
+    }
+    check_target(output, target_crc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint(mut value: usize, buffer: &mut Vec<u8>) {
+        loop {
+            let x = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                buffer.push(0x80 | x);
+                return;
+            }
+            buffer.push(x);
+            value -= 1;
+        }
+    }
+
+    fn with_footer(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+        patch.extend_from_slice(&crc32(source).to_le_bytes());
+        patch.extend_from_slice(&crc32(target).to_le_bytes());
+        patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn ips_records_rle_and_truncation() {
+        let rom = vec![0u8; 16];
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0x00, 0x00, 0x02, 0x00, 0x02, 0xAA, 0xBB]);
+        patch.extend_from_slice(&[0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x04, 0xCC]);
+        patch.extend_from_slice(b"EOF");
+        assert_eq!(
+            apply(&rom, &patch).unwrap(),
+            [0, 0, 0xAA, 0xBB, 0, 0, 0, 0, 0xCC, 0xCC, 0xCC, 0xCC, 0, 0, 0, 0]
+        );
+
+        /* 超出 ROM 的记录会扩展 ROM，EOF 之后的长度截断 ROM */
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0x00, 0x00, 0x12, 0x00, 0x01, 0x55]);
+        patch.extend_from_slice(b"EOF");
+        assert_eq!(apply(&rom, &patch).unwrap().len(), 0x13);
+        patch.extend_from_slice(&[0x00, 0x00, 0x04]);
+        assert_eq!(apply(&rom, &patch).unwrap(), [0; 4]);
+
+        assert_eq!(
+            apply(&rom, b"PATCH\x00\x00"),
+            Err(PatchError::Malformed("unexpected end of patch"))
+        );
+    }
+
+    #[test]
+    fn ups_patch_with_checksums() {
+        let rom: Vec<u8> = (0..32).collect();
+        let mut target = rom.clone();
+        target[3] = 0xFF;
+        target[4] = 0xFE;
+        target.extend_from_slice(&[0x11, 0x22]);
+
+        let mut patch = b"UPS1".to_vec();
+        varint(rom.len(), &mut patch);
+        varint(target.len(), &mut patch);
+        varint(3, &mut patch);
+        patch.extend_from_slice(&[3 ^ 0xFF, 4 ^ 0xFE, 0]);
+        varint(32 - 6, &mut patch);
+        patch.extend_from_slice(&[0x11, 0x22, 0]);
+        let patch = with_footer(patch, &rom, &target);
+        assert_eq!(apply(&rom, &patch).unwrap(), target);
+
+        let mut other = rom.clone();
+        other[0] = 1;
+        assert!(matches!(
+            apply(&other, &patch),
+            Err(PatchError::SourceMismatch { .. })
+        ));
+        let mut corrupted = patch.clone();
+        corrupted[8] ^= 1;
+        assert!(matches!(
+            apply(&rom, &corrupted),
+            Err(PatchError::PatchMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn bps_patch_with_all_commands() {
+        let rom = b"Hello, World!".to_vec();
+        let target = b"Hello, Hello, GB!!!!".to_vec();
+
+        let mut patch = b"BPS1".to_vec();
+        varint(rom.len(), &mut patch);
+        varint(target.len(), &mut patch);
+        varint(0, &mut patch);
+        /* SourceRead "Hello, " */
+        varint((7 - 1) << 2, &mut patch);
+        /* SourceCopy "Hello, " 从 source 偏移 0 */
+        varint((7 - 1) << 2 | 2, &mut patch);
+        varint(0, &mut patch);
+        /* TargetRead "GB!" */
+        varint((3 - 1) << 2 | 1, &mut patch);
+        patch.extend_from_slice(b"GB!");
+        /* TargetCopy 重叠复制最后一个 '!' */
+        varint((3 - 1) << 2 | 3, &mut patch);
+        varint(16 << 1, &mut patch);
+        let patch = with_footer(patch, &rom, &target);
+        assert_eq!(apply(&rom, &patch).unwrap(), target);
+
+        let mut wrong_target = patch.clone();
+        let len = wrong_target.len();
+        wrong_target[len - 8] ^= 1;
+        let crc = crc32(&wrong_target[..len - 4]);
+        wrong_target[len - 4..].copy_from_slice(&crc.to_le_bytes());
+        assert!(matches!(
+            apply(&rom, &wrong_target),
+            Err(PatchError::TargetMismatch { .. })
+        ));
+
+        assert_eq!(apply(&rom, b"NOPE"), Err(PatchError::UnknownFormat));
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    }
+
+    #[test]
+    fn malformed_sizes_are_rejected() {
+        let rom = vec![0u8; 16];
+        let header = |source: usize, target: usize| {
+            let mut patch = b"UPS1".to_vec();
+            varint(source, &mut patch);
+            varint(target, &mut patch);
+            with_footer(patch, &rom, &rom)
+        };
+        assert_eq!(
+            apply(&rom, &header(15, 16)),
+            Err(PatchError::Malformed("source size mismatch"))
+        );
+        assert_eq!(
+            apply(&rom, &header(16, MAX_TARGET_SIZE + 1)),
+            Err(PatchError::Malformed("target too large"))
+        );
+
+        /* 10 个字节的 varint 已经超出 64 位 */
+        let mut patch = b"BPS1".to_vec();
+        patch.extend_from_slice(&[0x7F; 9]);
+        patch.push(0x81);
+        let patch = with_footer(patch, &rom, &rom);
+        assert_eq!(
+            apply(&rom, &patch),
+            Err(PatchError::Malformed("number too large"))
+        );
+    }
+
+    #[test]
+    fn oversized_lengths_and_offsets_are_rejected() {
+        let rom = vec![0u8; 16];
+
+        /* metadata 长度接近 usize::MAX，不能在计算结束位置时溢出 */
+        let mut patch = b"BPS1".to_vec();
+        varint(16, &mut patch);
+        varint(16, &mut patch);
+        varint(usize::MAX - 1, &mut patch);
+        let patch = with_footer(patch, &rom, &rom);
+        assert_eq!(
+            apply(&rom, &patch),
+            Err(PatchError::Malformed("unexpected end of patch"))
+        );
+
+        /* 第一个空 hunk 之后 pos 为 1，再跳过接近 usize::MAX 的距离会溢出 */
+        let mut patch = b"UPS1".to_vec();
+        varint(16, &mut patch);
+        varint(16, &mut patch);
+        varint(0, &mut patch);
+        patch.push(0);
+        varint(usize::MAX, &mut patch);
+        patch.push(0);
+        let patch = with_footer(patch, &rom, &rom);
+        assert_eq!(
+            apply(&rom, &patch),
+            Err(PatchError::Malformed("hunk out of range"))
+        );
+    }
+}
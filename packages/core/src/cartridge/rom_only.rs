@@ -0,0 +1,78 @@
+use super::mapper::{Capabilities, Mapper};
+
+/**
+ * 没有 MBC 的卡带，0x08/0x09 型号在 0xA000-0xBFFF 直接接了最多 8KB RAM
+ */
+pub struct RomOnly {
+    ram: Vec<u8>,
+    battery: bool,
+}
+
+impl RomOnly {
+    pub fn new(ram_size: usize, battery: bool) -> Self {
+        RomOnly {
+            ram: vec![0; ram_size.min(0x2000)],
+            battery,
+        }
+    }
+}
+
+impl Mapper for RomOnly {
+    fn read(&self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            0x0000..=0x7FFF => rom[address as usize % rom.len()],
+            0xA000..=0xBFFF if !self.ram.is_empty() => {
+                self.ram[(address as usize & 0x1FFF) % self.ram.len()]
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) -> bool {
+        if let 0xA000..=0xBFFF = address {
+            if !self.ram.is_empty() {
+                let offset = (address as usize & 0x1FFF) % self.ram.len();
+                self.ram[offset] = value;
+                return true;
+            }
+        }
+        false
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            battery: self.battery,
+            ..Default::default()
+        }
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let len = self.ram.len().min(data.len());
+        self.ram[..len].copy_from_slice(&data[..len]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rom_with_ram() {
+        let rom: Vec<u8> = (0..0x8000).map(|i| (i >> 8) as u8).collect();
+        let mut mbc = RomOnly::new(0x2000, true);
+        mbc.write(0x2000, 0x02);
+        assert_eq!(mbc.read(&rom, 0x4100), 0x41);
+
+        mbc.write(0xA123, 0x5A);
+        assert_eq!(mbc.read(&rom, 0xA123), 0x5A);
+        assert_eq!(mbc.save_data()[0x123], 0x5A);
+
+        let mbc = RomOnly::new(0, false);
+        assert_eq!(mbc.read(&rom, 0xA000), 0xFF);
+        assert!(!mbc.capabilities().battery);
+    }
+}
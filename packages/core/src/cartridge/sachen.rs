@@ -0,0 +1,193 @@
+use super::header::NINTENDO_LOGO;
+use super::mapper::{Capabilities, Mapper};
+use std::cell::Cell;
+
+/* 每次启动时 boot ROM 会读取 0x30 字节的 logo */
+const LOGO_READS: u8 = 0x30;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Lock {
+    /* MMC2 上电时的状态，等待 DMG boot ROM 读完 logo */
+    Dmg,
+    /* 读取 0x01xx 时强制 A7 为 1，让 boot ROM 读到 0x0184 处的 logo */
+    Locked,
+    Unlocked,
+}
+
+/**
+ * 0x0100-0x01FF 内交换地址线 A0/A6 与 A1/A4，卡带的 header 以这种方式打乱存储
+ */
+pub fn unscramble(address: u16) -> u16 {
+    address & 0xFFAC
+        | (address & 0x40) >> 6
+        | (address & 0x10) >> 3
+        | (address & 0x02) << 3
+        | (address & 0x01) << 6
+}
+
+/**
+ * 按 CPU 看到的地址还原 0x0000-0x01FF，用于解析 Sachen 卡带的 header
+ */
+pub fn unscrambled_header(rom: &[u8]) -> Vec<u8> {
+    (0..0x200u16)
+        .map(|address| match address {
+            0x0100..=0x01FF => rom[unscramble(address) as usize],
+            _ => rom[address as usize],
+        })
+        .collect()
+}
+
+/**
+ * Sachen MMC1 / MMC2 无授权卡带。
+ * 启动时前 0x30 次读取 0x01xx 会被重定向到 0x0180-0x01FF，让 boot ROM 显示 Sachen 的 logo，
+ * 之后才读到（打乱存储的）Nintendo logo 通过校验。
+ * bank 切换方式类似 MBC1，base bank 与 mask 只能在 0x2000-0x3FFF 写入的 bank 的 bit 4-5 都为 1 时修改
+ */
+pub struct Sachen {
+    base_bank: u8,
+    mask: u8,
+    unmasked_bank: u8,
+    lock: Cell<Lock>,
+    transition: Cell<u8>,
+}
+
+impl Sachen {
+    pub fn new(mmc2: bool) -> Self {
+        Sachen {
+            base_bank: 0,
+            mask: 0,
+            unmasked_bank: 1,
+            lock: Cell::new(if mmc2 { Lock::Dmg } else { Lock::Locked }),
+            transition: Cell::new(0),
+        }
+    }
+
+    /* 原始 ROM 中的 logo 不正确，但按打乱后的地址读出的 logo 正确 */
+    pub fn detect(rom: &[u8]) -> bool {
+        if rom.len() < 0x200 || rom[0x104..0x134] == NINTENDO_LOGO {
+            return false;
+        }
+        unscrambled_header(rom)[0x104..0x134] == NINTENDO_LOGO
+    }
+
+    fn rom_bank(&self) -> usize {
+        ((self.unmasked_bank & !self.mask) | (self.base_bank & self.mask)) as usize
+    }
+
+    fn bank_select_enabled(&self) -> bool {
+        self.unmasked_bank & 0x30 == 0x30
+    }
+
+    /* boot ROM 读取 0x01xx 时推进解锁状态 */
+    fn logo_read(&self, address: u16) -> u16 {
+        let lock = self.lock.get();
+        if lock != Lock::Unlocked && address & 0xFF00 == 0x0100 {
+            let transition = self.transition.get() + 1;
+            if transition > LOGO_READS {
+                self.transition.set(0);
+                self.lock.set(match lock {
+                    Lock::Dmg => Lock::Locked,
+                    _ => Lock::Unlocked,
+                });
+                return address;
+            }
+            self.transition.set(transition);
+            if lock == Lock::Locked {
+                return address | 0x80;
+            }
+        }
+        address
+    }
+}
+
+impl Mapper for Sachen {
+    fn read(&self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => {
+                let mut address = self.logo_read(address);
+                if address & 0xFF00 == 0x0100 {
+                    address = unscramble(address);
+                }
+                let bank = (self.base_bank & self.mask) as usize;
+                rom[(bank * 0x4000 + address as usize) % rom.len()]
+            }
+            0x4000..=0x7FFF => {
+                rom[(self.rom_bank() * 0x4000 + (address as usize & 0x3FFF)) % rom.len()]
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) -> bool {
+        match address {
+            0x0000..=0x1FFF if self.bank_select_enabled() => self.base_bank = value,
+            0x2000..=0x3FFF => self.unmasked_bank = value.max(1),
+            0x4000..=0x5FFF if self.bank_select_enabled() => self.mask = value,
+            _ => {}
+        }
+        false
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    fn skip_boot(&mut self) {
+        self.lock.set(Lock::Unlocked);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scrambled_rom() -> Vec<u8> {
+        let mut rom: Vec<u8> = (0..4).flat_map(|bank| vec![bank; 0x4000]).collect();
+        /* CPU 在 0x0104 读到 Nintendo logo，在 0x0184 读到 Sachen 的 logo */
+        for i in 0..0x30 {
+            rom[unscramble(0x104 + i) as usize] = NINTENDO_LOGO[i as usize];
+            rom[unscramble(0x184 + i) as usize] = 0x5A;
+        }
+        rom
+    }
+
+    #[test]
+    fn boot_logo_then_unscrambled_header() {
+        let rom = scrambled_rom();
+        assert!(Sachen::detect(&rom));
+        assert_eq!(unscramble(unscramble(0x0153)), 0x0153);
+
+        let mbc = Sachen::new(false);
+        /* boot ROM 先读取 logo 显示在屏幕上，再读取一次进行校验 */
+        let shown: Vec<u8> = (0x104..0x134).map(|addr| mbc.read(&rom, addr)).collect();
+        assert_eq!(shown, [0x5A; 0x30]);
+        let checked: Vec<u8> = (0x104..0x134).map(|addr| mbc.read(&rom, addr)).collect();
+        assert_eq!(checked, NINTENDO_LOGO);
+
+        let mut mbc = Sachen::new(true);
+        mbc.skip_boot();
+        assert_eq!(mbc.read(&rom, 0x104), NINTENDO_LOGO[0]);
+    }
+
+    #[test]
+    fn masked_bank_switching() {
+        let rom: Vec<u8> = (0..0x20).flat_map(|bank| vec![bank; 0x4000]).collect();
+        let mut mbc = Sachen::new(false);
+        mbc.skip_boot();
+        mbc.write(0x2000, 0x00);
+        assert_eq!(mbc.read(&rom, 0x4000), 1);
+
+        /* bank 的 bit 4-5 不都为 1 时 base bank 与 mask 被锁定 */
+        mbc.write(0x0000, 0x10);
+        assert_eq!(mbc.read(&rom, 0x0000), 0);
+
+        mbc.write(0x2000, 0x30);
+        mbc.write(0x0000, 0x10);
+        mbc.write(0x4000, 0x18);
+        assert_eq!(mbc.read(&rom, 0x0000), 0x10);
+        mbc.write(0x2000, 0x03);
+        assert_eq!(mbc.read(&rom, 0x4000), 0x13);
+        mbc.write(0x2000, 0x0F);
+        assert_eq!(mbc.read(&rom, 0x4000), 0x17);
+    }
+}
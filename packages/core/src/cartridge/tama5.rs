@@ -0,0 +1,422 @@
+use super::{
+    clock::Clock,
+    mapper::{Capabilities, Mapper},
+};
+use crate::utils::bit;
+
+/* u64 时间戳 + 4 个 RTC page，每个 page 16 个 nibble 两两打包 */
+pub const RTC_FOOTER_SIZE: usize = 8 + 4 * 8;
+
+const RAM_SIZE: usize = 0x20;
+
+/* 通过 0xA001 选择、0xA000 写入的 4bit 寄存器 */
+const REG_BANK_LO: u8 = 0x0;
+const REG_BANK_HI: u8 = 0x1;
+const REG_WRITE_LO: u8 = 0x4;
+const REG_WRITE_HI: u8 = 0x5;
+const REG_ADDR_HI: u8 = 0x6;
+const REG_ADDR_LO: u8 = 0x7;
+const REG_READY: u8 = 0xA;
+const REG_READ_LO: u8 = 0xC;
+const REG_READ_HI: u8 = 0xD;
+
+/* TC8521AP 时间 page 中各 nibble 的位置 */
+const SECOND: usize = 0x0;
+const MINUTE: usize = 0x2;
+const HOUR: usize = 0x4;
+const DAY_OF_WEEK: usize = 0x6;
+const DAY: usize = 0x7;
+const MONTH: usize = 0x9;
+const YEAR: usize = 0xB;
+/* alarm page 中的 12/24 小时制选择与闰年计数 */
+const HOUR_24: usize = 0xA;
+const LEAP_YEAR: usize = 0xB;
+/* 所有 page 共用的 mode 寄存器，bit 3 为计时使能 */
+const MODE: usize = 0xD;
+
+/**
+ * 东芝 TC8521AP RTC 的寄存器，共 4 个 page：时间、闹钟和两个空闲存储
+ */
+#[derive(Clone, Copy)]
+struct RtcPages([[u8; 0x10]; 4]);
+
+impl RtcPages {
+    fn new() -> Self {
+        let mut pages = [[0; 0x10]; 4];
+        pages[0][DAY] = 1;
+        pages[0][MONTH] = 1;
+        pages[1][HOUR_24] = 1;
+        for page in pages.iter_mut() {
+            page[MODE] = 0x8;
+        }
+        RtcPages(pages)
+    }
+
+    fn is_running(&self) -> bool {
+        bit!(self.0[0][MODE], 3)
+    }
+
+    fn set_mode(&mut self, mode: u8) {
+        for page in self.0.iter_mut() {
+            page[MODE] = mode & 0xF;
+        }
+    }
+
+    fn bcd(&self, index: usize) -> u32 {
+        let time = &self.0[0];
+        (time[index] & 0xF) as u32 + (time[index + 1] & 0xF) as u32 * 10
+    }
+
+    fn set_bcd(&mut self, index: usize, value: u32) {
+        self.0[0][index] = (value % 10) as u8;
+        self.0[0][index + 1] = (value / 10 % 10) as u8;
+    }
+
+    /* 12 小时制时 hour 十位的 bit 1 表示 PM */
+    fn hours(&self) -> u32 {
+        if self.0[1][HOUR_24] & 1 != 0 {
+            return self.bcd(HOUR);
+        }
+        let time = &self.0[0];
+        let hours = (time[HOUR] & 0xF) as u32 + (time[HOUR + 1] & 0x1) as u32 * 10;
+        hours % 12 + if bit!(time[HOUR + 1], 1) { 12 } else { 0 }
+    }
+
+    fn set_hours(&mut self, hours: u32) {
+        if self.0[1][HOUR_24] & 1 != 0 {
+            return self.set_bcd(HOUR, hours);
+        }
+        let pm = hours >= 12;
+        let hours = match hours % 12 {
+            0 => 12,
+            hours => hours,
+        };
+        self.set_bcd(HOUR, hours);
+        self.0[0][HOUR + 1] |= (pm as u8) << 1;
+    }
+
+    fn days_in_month(&self) -> u32 {
+        match self.bcd(MONTH) {
+            2 if self.0[1][LEAP_YEAR] & 3 == 0 => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+
+    fn next_day(&mut self) {
+        self.0[0][DAY_OF_WEEK] = (self.0[0][DAY_OF_WEEK] + 1) % 7;
+        let day = self.bcd(DAY) + 1;
+        if day <= self.days_in_month() {
+            return self.set_bcd(DAY, day);
+        }
+        self.set_bcd(DAY, 1);
+        let month = self.bcd(MONTH) + 1;
+        if month <= 12 {
+            return self.set_bcd(MONTH, month);
+        }
+        self.set_bcd(MONTH, 1);
+        self.set_bcd(YEAR, (self.bcd(YEAR) + 1) % 100);
+        self.0[1][LEAP_YEAR] = (self.0[1][LEAP_YEAR] + 1) & 3;
+    }
+
+    fn advance(&mut self, seconds: u64) {
+        let time = self.bcd(SECOND) as u64
+            + self.bcd(MINUTE) as u64 * 60
+            + self.hours() as u64 * 3600
+            + seconds;
+        let time_of_day = (time % 86400) as u32;
+        self.set_bcd(SECOND, time_of_day % 60);
+        self.set_bcd(MINUTE, time_of_day / 60 % 60);
+        self.set_hours(time_of_day / 3600);
+        for _ in 0..time / 86400 {
+            self.next_day();
+        }
+    }
+}
+
+struct Tc8521 {
+    clock: Box<dyn Clock>,
+    last_time: u64,
+    pages: RtcPages,
+}
+
+impl Tc8521 {
+    /* 推算出当前时刻的寄存器值，不修改自身 */
+    fn current(&self) -> RtcPages {
+        let mut pages = self.pages;
+        if pages.is_running() {
+            pages.advance(self.clock.now().saturating_sub(self.last_time));
+        }
+        pages
+    }
+
+    fn update(&mut self) {
+        self.pages = self.current();
+        self.last_time = self.clock.now();
+    }
+}
+
+/**
+ * Bandai TAMA5，所有操作都通过 0xA001 选择寄存器、0xA000 读写 4bit 数据完成
+ */
+pub struct Tama5 {
+    ram: Vec<u8>,
+    registers: [u8; 0x10],
+    selected: u8,
+    rtc: Tc8521,
+}
+
+impl Tama5 {
+    pub fn new(clock: Box<dyn Clock>) -> Self {
+        Tama5 {
+            ram: vec![0; RAM_SIZE],
+            registers: [0; 0x10],
+            selected: 0,
+            rtc: Tc8521 {
+                last_time: clock.now(),
+                clock,
+                pages: RtcPages::new(),
+            },
+        }
+    }
+
+    fn rom_bank(&self) -> usize {
+        (self.registers[REG_BANK_LO as usize] | self.registers[REG_BANK_HI as usize] << 4) as usize
+    }
+
+    /* 命令存放在 ADDR_HI 的高 3 位，低位为 RAM 地址第 4 位 */
+    fn command(&self) -> u8 {
+        self.registers[REG_ADDR_HI as usize] >> 1
+    }
+
+    fn ram_address(&self) -> usize {
+        ((self.registers[REG_ADDR_HI as usize] & 1) << 4 | self.registers[REG_ADDR_LO as usize])
+            as usize
+    }
+
+    fn write_value(&self) -> u8 {
+        self.registers[REG_WRITE_LO as usize] | self.registers[REG_WRITE_HI as usize] << 4
+    }
+
+    /* 写入 ADDR_LO 时执行命令 */
+    fn execute(&mut self) {
+        let value = self.write_value();
+        match self.command() {
+            0x0 => {
+                let address = self.ram_address();
+                self.ram[address] = value;
+            }
+            0x2 => match self.ram_address() {
+                0x00 => {
+                    self.rtc.update();
+                    self.rtc.pages.set_mode(self.rtc.pages.0[0][MODE] & 0x7);
+                }
+                0x01 => {
+                    self.rtc.update();
+                    self.rtc.pages.set_mode(self.rtc.pages.0[0][MODE] | 0x8);
+                    self.rtc.pages.set_bcd(SECOND, 0);
+                }
+                0x04 => {
+                    self.rtc.update();
+                    self.rtc.pages.set_bcd(MINUTE, bcd_to_binary(value));
+                }
+                0x05 => {
+                    self.rtc.update();
+                    self.rtc.pages.set_hours(bcd_to_binary(value));
+                }
+                _ => {}
+            },
+            /* RTC 寄存器访问：WRITE_LO 为寄存器号，WRITE_HI 为数据，ADDR_LO 的高 3 位选择 page */
+            0x4 if self.registers[REG_ADDR_LO as usize] & 1 == 0 => {
+                let page = self.registers[REG_ADDR_LO as usize] as usize >> 1;
+                let index = self.registers[REG_WRITE_LO as usize] as usize;
+                let data = self.registers[REG_WRITE_HI as usize];
+                self.rtc.update();
+                match index & 0xF {
+                    MODE => self.rtc.pages.set_mode(data),
+                    index => self.rtc.pages.0[page & 3][index] = data,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn read_value(&self) -> u8 {
+        match self.command() {
+            0x1 => self.ram[self.ram_address()],
+            0x2 => match self.ram_address() {
+                0x06 => binary_to_bcd(self.rtc.current().bcd(MINUTE)),
+                0x07 => binary_to_bcd(self.rtc.current().hours()),
+                address => address as u8,
+            },
+            0x4 => {
+                let page = self.registers[REG_ADDR_LO as usize] as usize >> 1;
+                let index = self.registers[REG_WRITE_LO as usize] as usize;
+                self.rtc.current().0[page & 3][index]
+            }
+            _ => 0x00,
+        }
+    }
+}
+
+impl Mapper for Tama5 {
+    fn read(&self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => rom[address as usize],
+            0x4000..=0x7FFF => {
+                let offset = self.rom_bank() * 0x4000 + (address as usize & 0x3FFF);
+                rom[offset % rom.len()]
+            }
+            0xA000..=0xBFFF if address & 1 == 0 => match self.selected {
+                /* 游戏轮询该寄存器等待芯片就绪 */
+                REG_READY => 0xF1,
+                REG_READ_LO => 0xF0 | self.read_value() & 0xF,
+                REG_READ_HI => 0xF0 | self.read_value() >> 4,
+                _ => 0xF0,
+            },
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) -> bool {
+        match address {
+            0xA000..=0xBFFF if address & 1 != 0 => self.selected = value & 0xF,
+            0xA000..=0xBFFF => {
+                let register = self.selected;
+                self.registers[register as usize] = value & 0xF;
+                if register == REG_ADDR_LO {
+                    self.execute();
+                    /* 命令 0x0 写入 RAM */
+                    return self.command() == 0x0;
+                }
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            battery: true,
+            rtc: true,
+            ..Default::default()
+        }
+    }
+
+    #[cfg(test)]
+    fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.rtc.update();
+        self.rtc.last_time = clock.now();
+        self.rtc.clock = clock;
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        let mut data = self.ram.clone();
+        data.extend_from_slice(&self.rtc.clock.now().to_le_bytes());
+        for page in self.rtc.current().0.iter() {
+            data.extend(page.chunks(2).map(|nibbles| nibbles[0] | nibbles[1] << 4));
+        }
+        data
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let len = RAM_SIZE.min(data.len());
+        self.ram[..len].copy_from_slice(&data[..len]);
+
+        let footer = &data[len..];
+        if footer.len() != RTC_FOOTER_SIZE {
+            return;
+        }
+        self.rtc.last_time = u64::from_le_bytes(footer[0..8].try_into().unwrap());
+        for (page, packed) in self.rtc.pages.0.iter_mut().zip(footer[8..].chunks(8)) {
+            for (i, byte) in packed.iter().enumerate() {
+                page[i * 2] = byte & 0xF;
+                page[i * 2 + 1] = byte >> 4;
+            }
+        }
+        self.rtc.update();
+    }
+}
+
+fn bcd_to_binary(value: u8) -> u32 {
+    (value >> 4) as u32 * 10 + (value & 0xF) as u32
+}
+
+fn binary_to_bcd(value: u32) -> u8 {
+    (value / 10 * 16 + value % 10) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::clock::tests::FakeClock;
+
+    fn write_register(mbc: &mut Tama5, register: u8, value: u8) {
+        mbc.write(0xA001, register);
+        mbc.write(0xA000, value);
+    }
+
+    fn read_register(mbc: &mut Tama5, register: u8) -> u8 {
+        mbc.write(0xA001, register);
+        mbc.read(&[0], 0xA000) & 0xF
+    }
+
+    fn command(mbc: &mut Tama5, command: u8, address: u8, value: u8) {
+        write_register(mbc, REG_WRITE_LO, value & 0xF);
+        write_register(mbc, REG_WRITE_HI, value >> 4);
+        write_register(mbc, REG_ADDR_HI, command << 1 | address >> 4);
+        write_register(mbc, REG_ADDR_LO, address & 0xF);
+    }
+
+    fn read_byte(mbc: &mut Tama5, command: u8, address: u8) -> u8 {
+        write_register(mbc, REG_ADDR_HI, command << 1 | address >> 4);
+        write_register(mbc, REG_ADDR_LO, address & 0xF);
+        read_register(mbc, REG_READ_LO) | read_register(mbc, REG_READ_HI) << 4
+    }
+
+    #[test]
+    fn rom_banking_and_ram() {
+        let rom: Vec<u8> = (0..0x20).flat_map(|bank| vec![bank; 0x4000]).collect();
+        let mut mbc = Tama5::new(Box::new(FakeClock::default()));
+        assert_eq!(read_register(&mut mbc, REG_READY), 0x1);
+
+        write_register(&mut mbc, REG_BANK_LO, 0x3);
+        write_register(&mut mbc, REG_BANK_HI, 0x1);
+        assert_eq!(mbc.read(&rom, 0x4000), 0x13);
+
+        command(&mut mbc, 0x0, 0x1F, 0xA5);
+        assert_eq!(read_byte(&mut mbc, 0x1, 0x1F), 0xA5);
+        assert_eq!(mbc.save_data()[0x1F], 0xA5);
+    }
+
+    #[test]
+    fn rtc_runs_and_persists() {
+        let clock = FakeClock::default();
+        let mut mbc = Tama5::new(Box::new(clock.clone()));
+
+        command(&mut mbc, 0x2, 0x05, 0x23);
+        command(&mut mbc, 0x2, 0x04, 0x59);
+        command(&mut mbc, 0x2, 0x01, 0x00);
+        clock.advance(61);
+        assert_eq!(read_byte(&mut mbc, 0x2, 0x07), 0x00);
+        assert_eq!(read_byte(&mut mbc, 0x2, 0x06), 0x00);
+        /* 通过 RTC 寄存器访问读取 day 个位和 day of week */
+        write_register(&mut mbc, REG_WRITE_LO, DAY as u8);
+        assert_eq!(read_byte(&mut mbc, 0x4, 0x01) & 0xF, 2);
+        write_register(&mut mbc, REG_WRITE_LO, DAY_OF_WEEK as u8);
+        assert_eq!(read_byte(&mut mbc, 0x4, 0x01) & 0xF, 1);
+
+        let save = mbc.save_data();
+        assert_eq!(save.len(), RAM_SIZE + RTC_FOOTER_SIZE);
+
+        clock.advance(31 * 86400);
+        let mut restored = Tama5::new(Box::new(clock.clone()));
+        restored.load_save_data(&save);
+        write_register(&mut restored, REG_WRITE_LO, MONTH as u8);
+        assert_eq!(read_byte(&mut restored, 0x4, 0x01) & 0xF, 2);
+        write_register(&mut restored, REG_WRITE_LO, DAY as u8);
+        assert_eq!(read_byte(&mut restored, 0x4, 0x01) & 0xF, 2);
+        assert_eq!(read_byte(&mut restored, 0x2, 0x07), 0x00);
+    }
+}
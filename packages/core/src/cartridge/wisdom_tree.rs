@@ -0,0 +1,67 @@
+use super::mapper::{Capabilities, Mapper};
+
+/**
+ * Wisdom Tree 的无授权卡带，向 0x0000-0x3FFF 写入时由地址的低 8 位
+ * 选择映射到 0x0000-0x7FFF 的 32KB bank，写入的值被忽略
+ */
+pub struct WisdomTree {
+    bank: usize,
+}
+
+impl WisdomTree {
+    pub fn new() -> Self {
+        WisdomTree { bank: 0 }
+    }
+
+    /* 卡带类型为 0x00 或 0xC0，且 ROM 中带有 Wisdom Tree 的字样 */
+    pub fn detect(cart_type: u8, rom: &[u8]) -> bool {
+        matches!(cart_type, 0x00 | 0xC0)
+            && rom.len() > 0x8000
+            && rom
+                .windows(11)
+                .any(|window| window == b"WISDOM TREE" || window == b"WISDOM\0TREE")
+    }
+}
+
+impl Mapper for WisdomTree {
+    fn read(&self, rom: &[u8], address: u16) -> u8 {
+        match address {
+            0x0000..=0x7FFF => rom[(self.bank * 0x8000 + address as usize) % rom.len()],
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, address: u16, _value: u8) -> bool {
+        if let 0x0000..=0x3FFF = address {
+            self.bank = address as usize & 0xFF;
+        }
+        false
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address_selects_32k_bank() {
+        let mut rom: Vec<u8> = (0..8).flat_map(|bank| vec![bank; 0x8000]).collect();
+        let mut mbc = WisdomTree::new();
+        assert_eq!(mbc.read(&rom, 0x7FFF), 0);
+
+        mbc.write(0x0003, 0xFF);
+        assert_eq!(mbc.read(&rom, 0x0000), 3);
+        assert_eq!(mbc.read(&rom, 0x4000), 3);
+        mbc.write(0x2105, 0x00);
+        assert_eq!(mbc.read(&rom, 0x7FFF), 5);
+
+        assert!(!WisdomTree::detect(0x00, &rom));
+        rom[0x1234..0x123F].copy_from_slice(b"WISDOM TREE");
+        assert!(WisdomTree::detect(0xC0, &rom));
+        assert!(!WisdomTree::detect(0x01, &rom));
+    }
+}
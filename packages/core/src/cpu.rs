@@ -85,7 +85,7 @@ macro_rules! concat_u16 {
 pub struct Bus {
     interrupt: InterruptContext,
     timer: Timer,
-    cartridge: Cartridge,
+    pub cartridge: Cartridge,
     wram: RAM<0x2000, 0xC000>,
     hram: RAM<0x80, 0xFF80>,
     pub ppu: PPU,
@@ -94,6 +94,8 @@ pub struct Bus {
 
 impl Bus {
     pub fn new(cartridge: Cartridge) -> Self {
+        let mut io = IO::create();
+        io.cgb = cartridge.header().is_cgb();
         Bus {
             interrupt: InterruptContext::create(),
             timer: Timer::create(),
@@ -101,7 +103,7 @@ impl Bus {
             wram: RAM::create(),
             hram: RAM::create(),
             ppu: PPU::create(),
-            io: IO::create(),
+            io,
         }
     }
 
@@ -146,29 +148,20 @@ impl Bus {
         }
     }
 
-    fn read_16(&self, address: u16) -> u16 {
-        let lo = self.read(address) as u16;
-        let hi = self.read(address + 1) as u16;
-
-        lo | (hi << 8)
-    }
-
-    fn write_16(&mut self, address: u16, value: u16) {
-        let lo = (value & 0x00FF) as u8;
-        let hi = ((value & 0xFF00) >> 8) as u8;
-        self.write(address, lo);
-        self.write(address + 1, hi);
-    }
-
     pub fn tick(&mut self) {
-        for _ in 0..4 {
+        /* 双倍速下 CPU 与 timer 加倍，PPU 不变，每个 M-cycle 只有 2 个 PPU tick */
+        let ppu_ticks = if self.io.speed.double { 2 } else { 4 };
+        for i in 0..4 {
             self.timer.tick(|interrupt| {
                 self.interrupt.request_interrupt(interrupt);
             });
-            self.ppu.tick(&mut |interrupt| {
-                self.interrupt.request_interrupt(interrupt);
-            });
+            if i < ppu_ticks {
+                self.ppu.tick(&mut |interrupt| {
+                    self.interrupt.request_interrupt(interrupt);
+                });
+            }
         }
+        self.cartridge.tick();
 
         self.ppu.dma_tick(|ppu, from| match from {
             0x0000..=0x7FFF => self.cartridge.read(from),
@@ -180,13 +173,39 @@ impl Bus {
     }
 }
 
+/**
+ * 执行非法指令后 CPU 卡死，直到断电
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lockup {
+    pub pc: u16,
+    pub opcode: u8,
+}
+
+impl std::fmt::Display for Lockup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "CPU locked at PC={:04X}, opcode={:02X}",
+            self.pc, self.opcode
+        )
+    }
+}
+
 pub struct CpuContext {
     pub registers: Registers,
 
     pub halted: bool,
+    /* STOP 低功耗模式，时钟停止直到有按键按下 */
+    pub stopped: bool,
     pub stepping: bool,
 
     pub enabling_ime: bool,
+    /* HALT bug：下一次取指令时 PC 不增加 */
+    pub halt_bug: bool,
+
+    pub locked: Option<Lockup>,
+    lock_callback: Option<Box<dyn FnMut(Lockup)>>,
 
     pub bus: Bus,
 }
@@ -197,9 +216,14 @@ impl CpuContext {
             registers: Registers::default(),
 
             halted: true,
+            stopped: false,
             stepping: false,
 
             enabling_ime: false,
+            halt_bug: false,
+
+            locked: None,
+            lock_callback: None,
 
             bus: Bus::new(cartridge),
         }
@@ -217,6 +241,44 @@ impl CpuContext {
         self.registers.pc = 0x0100;
         self.registers.sp = 0xFFFE;
         self.halted = false;
+        /* 没有 boot ROM，直接从 boot ROM 结束时的状态开始 */
+        self.bus.cartridge.skip_boot();
+    }
+
+    pub fn set_lock_callback(&mut self, callback: Box<dyn FnMut(Lockup)>) {
+        self.lock_callback = Some(callback);
+    }
+
+    fn lock_up(&mut self, pc: u16, opcode: u8) {
+        let lockup = Lockup { pc, opcode };
+        self.locked = Some(lockup);
+        if let Some(callback) = &mut self.lock_callback {
+            callback(lockup);
+        }
+    }
+
+    /**
+     * 中断处理共 5 个 M-cycle：2 个空闲周期，压入 PC 高字节，压入 PC 低字节，跳转。
+     * 压入高字节之后才决定跳转到哪个中断，如果高字节写入 IE (0xFFFF) 取消了中断，则跳转到 0x0000
+     */
+    fn dispatch_interrupt(&mut self) {
+        self.bus.interrupt.master_enabled = false;
+        self.halted = false;
+        /* EI 后紧跟 HALT 触发 HALT bug 时，中断返回到 HALT 本身 */
+        if self.halt_bug {
+            self.registers.pc -= 1;
+            self.halt_bug = false;
+        }
+        self.emu_cycles(2);
+
+        let pc = self.registers.pc;
+        self.stack_push((pc >> 8) as u8);
+
+        let address = self.bus.interrupt.handle_interrupts();
+        self.stack_push(pc as u8);
+
+        self.registers.pc = address.unwrap_or(0x0000);
+        self.emu_cycles(1);
     }
 
     fn read_reg(&self, register: &Register) -> DataKind {
@@ -286,16 +348,16 @@ impl CpuContext {
     }
 
     pub fn stack_push(&mut self, data: u8) {
-        self.registers.sp -= 1;
-        self.bus.write(self.registers.sp, data);
+        self.registers.sp = self.registers.sp.wrapping_sub(1);
+        self.write_cycle(self.registers.sp, data);
     }
     pub fn stack_push_16(&mut self, data: u16) {
         self.stack_push(((data >> 8) & 0xFF) as u8);
         self.stack_push((data & 0xFF) as u8);
     }
     pub fn stack_pop(&mut self) -> u8 {
-        let data = self.bus.read(self.registers.sp);
-        self.registers.sp += 1;
+        let data = self.read_cycle(self.registers.sp);
+        self.registers.sp = self.registers.sp.wrapping_add(1);
         data
     }
     pub fn stack_pop_16(&mut self) -> u16 {
@@ -310,8 +372,20 @@ impl CpuContext {
         }
     }
 
-    fn fetch_instruction(&mut self) -> &'static Instruction {
-        let current_opcode = self.bus.read(self.registers.pc);
+    /* 每次读写内存占用一个 M-cycle，访问发生在这个 M-cycle 的末尾 */
+    fn read_cycle(&mut self, address: u16) -> u8 {
+        self.emu_cycles(1);
+        self.bus.read(address)
+    }
+
+    fn write_cycle(&mut self, address: u16, value: u8) {
+        self.emu_cycles(1);
+        self.bus.write(address, value);
+    }
+
+    /* 返回取出的 opcode 与对应的指令 */
+    fn fetch_instruction(&mut self) -> (u8, &'static Instruction) {
+        let current_opcode = self.read_cycle(self.registers.pc);
         macro_rules! print_flag {
             ($c:literal, $i:literal) => {
                 if self.registers.f & (1 << $i) != 0 {
@@ -344,51 +418,42 @@ impl CpuContext {
         //     concat_u16!(self.registers.h, self.registers.l),
         //     self.registers.sp,
         // );
-        self.registers.pc += 1;
-        instruction
+        if self.halt_bug {
+            self.halt_bug = false;
+        } else {
+            self.registers.pc += 1;
+        }
+        (current_opcode, instruction)
     }
 
     fn fetch_data(&mut self, addressing_mode: &AddressingMode) -> DataKind {
         match addressing_mode {
             AddressingMode::R(register) => self.read_reg(register),
             AddressingMode::MR(register) => {
-                let data = DataKind::D8(self.bus.read(match self.read_reg(register) {
+                DataKind::D8(self.read_cycle(match self.read_reg(register) {
                     DataKind::D8(address) => 0xFF00 + address as u16,
                     DataKind::D16(address) => address,
-                }));
-                self.emu_cycles(1);
-                data
+                }))
             }
             AddressingMode::A8 | AddressingMode::D8 => {
-                let data = self.bus.read(self.registers.pc);
+                let data = self.read_cycle(self.registers.pc);
                 self.registers.pc += 1;
-                self.emu_cycles(1);
                 match addressing_mode {
-                    AddressingMode::A8 => {
-                        let data = DataKind::D8(self.bus.read(0xFF00 | data as u16));
-                        self.emu_cycles(1);
-                        data
-                    }
+                    AddressingMode::A8 => DataKind::D8(self.read_cycle(0xFF00 | data as u16)),
                     AddressingMode::D8 => DataKind::D8(data),
                     _ => unreachable!(),
                 }
             }
             AddressingMode::A16 | AddressingMode::D16 => {
-                let lo = self.bus.read(self.registers.pc) as u16;
+                let lo = self.read_cycle(self.registers.pc) as u16;
                 self.registers.pc += 1;
-                self.emu_cycles(1);
 
-                let hi = self.bus.read(self.registers.pc) as u16;
+                let hi = self.read_cycle(self.registers.pc) as u16;
                 self.registers.pc += 1;
-                self.emu_cycles(1);
 
                 let data = lo | (hi << 8);
                 match addressing_mode {
-                    AddressingMode::A16 => {
-                        let data = DataKind::D8(self.bus.read(data));
-                        self.emu_cycles(1);
-                        data
-                    }
+                    AddressingMode::A16 => DataKind::D8(self.read_cycle(data)),
                     AddressingMode::D16 => DataKind::D16(data),
                     _ => unreachable!(),
                 }
@@ -401,16 +466,16 @@ impl CpuContext {
             AddressingMode::R(register) => LeftDataKind::R(*register),
             AddressingMode::MR(register) => LeftDataKind::MR(*register),
             AddressingMode::A8 => {
-                let data = self.bus.read(self.registers.pc);
+                let data = self.read_cycle(self.registers.pc);
                 self.registers.pc += 1;
-                self.emu_cycles(1);
                 LeftDataKind::A16(0xFF00 | data as u16)
             }
             AddressingMode::A16 => {
-                let data = self.bus.read_16(self.registers.pc);
-                self.registers.pc += 2;
-                self.emu_cycles(2);
-                LeftDataKind::A16(data)
+                let lo = self.read_cycle(self.registers.pc);
+                self.registers.pc += 1;
+                let hi = self.read_cycle(self.registers.pc);
+                self.registers.pc += 1;
+                LeftDataKind::A16(concat_u16!(hi, lo))
             }
             _ => unreachable!(),
         }
@@ -424,29 +489,19 @@ impl CpuContext {
                     DataKind::D8(address) => 0xFF00 + address as u16,
                     DataKind::D16(address) => address,
                 };
-                match data {
-                    DataKind::D8(data) => {
-                        self.bus.write(address, *data);
-                        self.emu_cycles(1);
-                    }
-                    DataKind::D16(data) => {
-                        self.bus.write_16(address, *data);
-                        self.emu_cycles(2);
-                    }
-                    _ => unreachable!(),
-                }
+                self.write_memory(address, data);
+            }
+            LeftDataKind::A16(address) => self.write_memory(*address, data),
+        }
+    }
+
+    fn write_memory(&mut self, address: u16, data: &DataKind) {
+        match data {
+            DataKind::D8(data) => self.write_cycle(address, *data),
+            DataKind::D16(data) => {
+                self.write_cycle(address, *data as u8);
+                self.write_cycle(address.wrapping_add(1), (*data >> 8) as u8);
             }
-            LeftDataKind::A16(address) => match data {
-                DataKind::D8(data) => {
-                    self.bus.write(*address, *data);
-                    self.emu_cycles(1);
-                }
-                DataKind::D16(data) => {
-                    self.bus.write_16(*address, *data);
-                    self.emu_cycles(2);
-                }
-                _ => unreachable!(),
-            },
         }
     }
 
@@ -454,12 +509,12 @@ impl CpuContext {
         macro_rules! goto_addr {
             ($cond:expr, $addr:expr, $push:expr) => {{
                 if self.check_condition($cond) {
+                    let addr = $addr;
+                    self.emu_cycles(1);
                     if $push {
-                        self.emu_cycles(2);
                         self.stack_push_16(self.registers.pc);
                     }
-                    self.registers.pc = $addr;
-                    self.emu_cycles(1);
+                    self.registers.pc = addr;
                 }
             }};
         }
@@ -534,24 +589,41 @@ impl CpuContext {
                 set_flags!(self.registers.a == 0, -1, 0, c);
             }
             Instruction::STOP => {
-                unimplemented!("STOP")
+                /* STOP 是 2 字节指令，跳过后面的一个字节 */
+                self.registers.pc = self.registers.pc.wrapping_add(1);
+                self.bus.timer.div = 0;
+                if self.bus.io.cgb && self.bus.io.speed.armed {
+                    self.bus.io.speed.armed = false;
+                    self.bus.io.speed.double = !self.bus.io.speed.double;
+                } else if self.bus.io.joypad.lines() == 0x0F {
+                    /* 已有按键按下时不会进入 STOP */
+                    self.stopped = true;
+                    self.bus.ppu.blank();
+                }
             }
             /* Interrupt-related instructions */
             Instruction::EI => {
                 /* 由于 EI 指令要求在下一个指令结束才设置 IME，先存到 enabling_ime */
                 self.enabling_ime = true;
             }
-            Instruction::DI => self.bus.interrupt.master_enabled = false,
-            Instruction::HALT => self.halted = true,
+            Instruction::DI => {
+                self.bus.interrupt.master_enabled = false;
+                self.enabling_ime = false;
+            }
+            Instruction::HALT => {
+                if !self.bus.interrupt.master_enabled && self.bus.interrupt.is_pending() {
+                    /* IME 为 0 且已有中断等待时不会进入 HALT，并且下一个字节会被读取两次 */
+                    self.halt_bug = true;
+                } else {
+                    self.halted = true;
+                }
+            }
             /* Jumps and subroutine instructions */
             Instruction::JP(condition) => {
                 let addr = self.fetch_data(&AddressingMode::D16).into();
                 goto_addr!(condition, addr, false);
             }
-            Instruction::JPHL => {
-                let addr = self.read_reg(&Register::HL).into();
-                goto_addr!(&Condition::None, addr, false);
-            }
+            Instruction::JPHL => self.registers.pc = self.read_reg(&Register::HL).into(),
             Instruction::JR(condition) => {
                 let rel: u8 = self.fetch_data(&AddressingMode::D8).into();
                 goto_addr!(
@@ -571,27 +643,11 @@ impl CpuContext {
                 if Condition::None != *condition {
                     self.emu_cycles(1);
                 }
-                goto_addr!(
-                    condition,
-                    {
-                        let addr = self.stack_pop_16();
-                        self.emu_cycles(2);
-                        addr
-                    },
-                    false
-                );
+                goto_addr!(condition, self.stack_pop_16(), false);
             }
             Instruction::RETI => {
                 self.bus.interrupt.master_enabled = true;
-                goto_addr!(
-                    &Condition::None,
-                    {
-                        let addr = self.stack_pop_16();
-                        self.emu_cycles(2);
-                        addr
-                    },
-                    false
-                );
+                goto_addr!(&Condition::None, self.stack_pop_16(), false);
             }
             Instruction::RST(vec) => {
                 goto_addr!(&Condition::None, *vec as u16, true);
@@ -599,75 +655,59 @@ impl CpuContext {
             /* Stack manipulation instructions */
             Instruction::PUSH(register) => match self.read_reg(register) {
                 DataKind::D16(data) => {
-                    let hi = (data >> 8) as u8;
-                    self.emu_cycles(1);
-                    self.stack_push(hi);
-
-                    let lo = data as u8;
-                    self.emu_cycles(1);
-                    self.stack_push(lo);
-
                     self.emu_cycles(1);
+                    self.stack_push_16(data);
                 }
                 DataKind::D8(_) => unreachable!(),
             },
             Instruction::POP(register) => {
-                let lo = self.stack_pop();
-                self.emu_cycles(1);
-                let hi = self.stack_pop();
-                self.emu_cycles(1);
-
-                let value = ((hi as u16) << 8) | lo as u16;
+                let value = self.stack_pop_16();
                 self.write_reg(register, value);
             }
             Instruction::POPAF => {
-                let lo = self.stack_pop();
-                self.emu_cycles(1);
-                let hi = self.stack_pop();
-                self.emu_cycles(1);
-
-                let value = ((hi as u16) << 8) as u16 | lo as u16;
+                let value = self.stack_pop_16();
                 self.write_reg(&Register::AF, value & 0xFFF0);
             }
             /* Load instructions */
             Instruction::LD(left_mode, right_mode) => {
                 let left = self.fetch_left_data(left_mode);
                 let right = self.fetch_data(right_mode);
+                /* LD SP,HL 需要额外一个周期 */
+                if let AddressingMode::R(Register::HL) = right_mode {
+                    self.emu_cycles(1);
+                }
                 self.write_data(&left, &right);
             }
             Instruction::LDI1 => {
                 // LD (HL+),A
                 let hl = self.read_reg(&Register::HL).into();
                 let a = self.registers.a;
-                self.bus.write(hl, a);
-                self.emu_cycles(1);
+                self.write_cycle(hl, a);
                 self.write_reg(&Register::HL, hl + 1);
             }
             Instruction::LDI2 => {
                 // LD A,(HL+)
                 let hl = self.read_reg(&Register::HL).into();
-                self.registers.a = self.bus.read(hl);
-                self.emu_cycles(1);
+                self.registers.a = self.read_cycle(hl);
                 self.write_reg(&Register::HL, hl + 1);
             }
             Instruction::LDD1 => {
                 // LD (HL-),A
                 let hl = self.read_reg(&Register::HL).into();
                 let a = self.registers.a;
-                self.bus.write(hl, a);
-                self.emu_cycles(1);
+                self.write_cycle(hl, a);
                 self.write_reg(&Register::HL, hl - 1);
             }
             Instruction::LDD2 => {
                 // LD A,(HL-)
                 let hl = self.read_reg(&Register::HL).into();
-                self.registers.a = self.bus.read(hl);
-                self.emu_cycles(1);
+                self.registers.a = self.read_cycle(hl);
                 self.write_reg(&Register::HL, hl - 1);
             }
             Instruction::LDHL => {
                 let rel: u8 = self.fetch_data(&AddressingMode::D8).into();
                 let sp: u16 = self.registers.sp;
+                self.emu_cycles(1);
                 self.write_reg(&Register::HL, sp.wrapping_add_signed(rel as i8 as i16));
                 let h = ((sp & 0xF) + (rel as u16 & 0xF)) >= 0x10;
                 let c = ((sp & 0xFF) + (rel as u16 & 0xFF)) >= 0x100;
@@ -701,21 +741,15 @@ impl CpuContext {
             Instruction::INCHL => {
                 let register = &Register::HL;
                 let addr: u16 = self.read_reg(register).into();
-                let (data, _) = self.bus.read(addr).overflowing_add(1);
-                self.emu_cycles(1);
-
-                self.bus.write(addr, data);
-                self.emu_cycles(1);
+                let (data, _) = self.read_cycle(addr).overflowing_add(1);
+                self.write_cycle(addr, data);
 
                 set_flags!(data == 0, 0, (data & 0x0F) == 0, -1);
             }
             Instruction::DECHL => {
                 let addr: u16 = self.read_reg(&Register::HL).into();
-                let (data, _) = self.bus.read(addr).overflowing_sub(1);
-                self.emu_cycles(1);
-
-                self.bus.write(addr, data);
-                self.emu_cycles(1);
+                let (data, _) = self.read_cycle(addr).overflowing_sub(1);
+                self.write_cycle(addr, data);
 
                 set_flags!(data == 0, 1, (data & 0x0F) == 0x0F, -1);
             }
@@ -748,7 +782,7 @@ impl CpuContext {
             Instruction::ADDSP => {
                 let data: u8 = self.fetch_data(&AddressingMode::D8).into();
                 let sp = self.registers.sp;
-                self.emu_cycles(1);
+                self.emu_cycles(2);
                 let (new_data, _) = sp.overflowing_add_signed(data as i8 as i16);
                 let h = (sp & 0x0F) + (data as u16 & 0x0F) >= 0x10;
                 let c = (sp & 0xFF) + (data as u16 & 0xFF) >= 0x100;
@@ -849,11 +883,7 @@ impl CpuContext {
                 macro_rules! read_reg {
                     ($r:expr) => {{
                         let d: u8 = match $r {
-                            Register::HL => {
-                                let data = self.bus.read(self.read_reg(&Register::HL).into());
-                                self.emu_cycles(1);
-                                data
-                            }
+                            Register::HL => self.read_cycle(self.read_reg(&Register::HL).into()),
                             reg => self.read_reg(reg).into(),
                         };
                         d
@@ -865,8 +895,7 @@ impl CpuContext {
                         let val = $v;
                         match $r {
                             Register::HL => {
-                                self.bus.write(self.read_reg(&Register::HL).into(), val);
-                                self.emu_cycles(1);
+                                self.write_cycle(self.read_reg(&Register::HL).into(), val)
                             }
                             reg => self.write_reg(reg, val as u16),
                         };
@@ -874,7 +903,6 @@ impl CpuContext {
                 }
 
                 let opcode: u8 = self.fetch_data(&AddressingMode::D8).into();
-                self.emu_cycles(1);
                 match CBInstruction::from(opcode) {
                     CBInstruction::BIT(bit, reg) => {
                         let data: u8 = read_reg!(reg);
@@ -952,31 +980,297 @@ impl CpuContext {
     }
 
     pub fn step(&mut self) -> bool {
-        if !self.halted {
-            let instruction = self.fetch_instruction();
-            self.emu_cycles(1);
-            self.execute(instruction);
-        } else {
+        if self.locked.is_some() {
+            /* CPU 卡死后不再执行指令也不响应中断，PPU 与 timer 照常运行 */
             self.emu_cycles(1);
+            return true;
+        }
 
-            if self.bus.interrupt.flag != 0 {
-                self.halted = false
+        if self.stopped {
+            /* 选中的按键线变为低电平时唤醒，同时产生 joypad 中断 */
+            if self.bus.io.joypad.lines() != 0x0F {
+                self.stopped = false;
+                self.bus.interrupt.request_interrupt(InterruptKind::JoyPad);
             }
+            self.emu_cycles(1);
+            return true;
         }
 
-        if self.bus.interrupt.master_enabled {
-            if let Some(address) = self.bus.interrupt.handle_interrupts() {
-                self.stack_push_16(self.registers.pc);
-                self.registers.pc = address;
-                self.halted = false;
+        /* 上一条指令是 EI 时，在这条指令结束后设置 IME */
+        let enabling_ime = self.enabling_ime;
+
+        if !self.halted {
+            let pc = self.registers.pc;
+            let (opcode, instruction) = self.fetch_instruction();
+            match instruction {
+                Instruction::None => {
+                    self.lock_up(pc, opcode);
+                    return true;
+                }
+                instruction => self.execute(instruction),
+            }
+        } else {
+            self.emu_cycles(1);
+
+            /* 不论 IME 是否开启，IE & IF 不为 0 时都会退出 HALT */
+            if self.bus.interrupt.is_pending() {
+                self.halted = false
             }
         }
 
-        if self.enabling_ime {
+        if enabling_ime && self.enabling_ime {
             self.bus.interrupt.master_enabled = true;
             self.enabling_ime = false;
         }
 
+        if self.bus.interrupt.master_enabled && self.bus.interrupt.is_pending() {
+            self.dispatch_interrupt();
+        }
+
         return true;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, rc::Rc};
+
+    fn cpu_with_program(program: &[u8]) -> CpuContext {
+        let mut data = vec![0; 0x8000];
+        data[0x100..0x100 + program.len()].copy_from_slice(program);
+        let mut cpu = CpuContext::create(Cartridge::from(data).unwrap());
+        cpu.init();
+        cpu
+    }
+
+    fn stack_top(cpu: &CpuContext) -> u16 {
+        let sp = cpu.registers.sp;
+        concat_u16!(cpu.bus.read(sp + 1), cpu.bus.read(sp))
+    }
+
+    #[test]
+    fn stop_waits_for_joypad() {
+        /* LD A,0x20; LDH (0x00),A; STOP 0; INC A */
+        let mut cpu = cpu_with_program(&[0x3E, 0x20, 0xE0, 0x00, 0x10, 0x00, 0x3C]);
+        let pressed = Rc::new(Cell::new(0u8));
+        let source = Rc::clone(&pressed);
+        cpu.bus.io.joypad.set_source(Box::new(move || source.get()));
+        for _ in 0..3 {
+            cpu.step();
+        }
+        assert!(cpu.stopped);
+        assert_eq!(cpu.registers.pc, 0x106);
+        assert_eq!(cpu.bus.read(0xFF04), 0);
+
+        /* 未被选中的功能键不会唤醒，等待期间时钟照常推进 */
+        pressed.set(0x10);
+        let tick = cpu.bus.timer.tick;
+        for _ in 0..100 {
+            cpu.step();
+        }
+        assert!(cpu.stopped);
+        assert_eq!(cpu.bus.timer.tick, tick + 400);
+
+        pressed.set(0x08);
+        cpu.step();
+        assert!(!cpu.stopped);
+        assert_ne!(cpu.bus.interrupt.flag & InterruptKind::JoyPad as u8, 0);
+        cpu.step();
+        assert_eq!(cpu.registers.a, 0x21);
+    }
+
+    #[test]
+    fn halt_wakes_on_enabled_interrupt() {
+        /* HALT; INC A */
+        let mut cpu = cpu_with_program(&[0x76, 0x3C]);
+        cpu.bus.write(0xFF0F, 0x04);
+        cpu.step();
+        cpu.step();
+        assert!(cpu.halted);
+
+        cpu.bus.write(0xFFFF, 0x04);
+        cpu.step();
+        assert!(!cpu.halted);
+        cpu.step();
+        assert_eq!(cpu.registers.a, 0x02);
+        assert_eq!(cpu.registers.pc, 0x102);
+    }
+
+    #[test]
+    fn halt_bug_reads_next_byte_twice() {
+        /* LD A,1; LDH (0xFF),A; LDH (0x0F),A; HALT; INC A */
+        let mut cpu = cpu_with_program(&[0x3E, 0x01, 0xE0, 0xFF, 0xE0, 0x0F, 0x76, 0x3C]);
+        for _ in 0..4 {
+            cpu.step();
+        }
+        assert!(!cpu.halted);
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.registers.a, 0x03);
+        assert_eq!(cpu.registers.pc, 0x108);
+    }
+
+    #[test]
+    fn ei_then_halt_returns_to_halt() {
+        /* EI; HALT */
+        let mut cpu = cpu_with_program(&[0xFB, 0x76]);
+        cpu.bus.write(0xFFFF, 0x01);
+        cpu.bus.write(0xFF0F, 0x01);
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.registers.pc, 0x40);
+        assert_eq!(stack_top(&cpu), 0x101);
+        assert!(!cpu.halted);
+    }
+
+    #[test]
+    fn locked_cpu_ignores_pending_interrupt() {
+        /* IME = 1 且 timer 中断已经挂起 */
+        let mut cpu = cpu_with_program(&[0xD3]);
+        cpu.bus.interrupt.enable = InterruptKind::Timer as u8;
+        cpu.bus.interrupt.flag = InterruptKind::Timer as u8;
+        cpu.bus.interrupt.master_enabled = true;
+        cpu.step();
+        assert!(cpu.locked.is_some());
+        assert_eq!(cpu.registers.pc, 0x101);
+        assert_eq!(cpu.registers.sp, 0xFFFE);
+
+        /* EI 紧跟非法指令时 IME 不会被打开 */
+        let mut cpu = cpu_with_program(&[0xFB, 0xD3]);
+        cpu.bus.interrupt.enable = InterruptKind::Timer as u8;
+        cpu.bus.interrupt.flag = InterruptKind::Timer as u8;
+        for _ in 0..4 {
+            cpu.step();
+        }
+        assert!(!cpu.bus.interrupt.master_enabled);
+        assert_eq!(cpu.registers.pc, 0x102);
+        assert_eq!(cpu.registers.sp, 0xFFFE);
+    }
+
+    #[test]
+    fn illegal_opcode_locks_up() {
+        /* LD A,4; LDH (0xFF),A; EI; illegal 0xD3 */
+        let mut cpu = cpu_with_program(&[0x3E, 0x04, 0xE0, 0xFF, 0xFB, 0xD3]);
+        let reported = Rc::new(Cell::new(None));
+        let recorder = Rc::clone(&reported);
+        cpu.set_lock_callback(Box::new(move |lockup| recorder.set(Some(lockup))));
+        for _ in 0..4 {
+            cpu.step();
+        }
+        let lockup = Lockup {
+            pc: 0x105,
+            opcode: 0xD3,
+        };
+        assert_eq!(cpu.locked, Some(lockup));
+        assert_eq!(reported.get(), Some(lockup));
+        assert_eq!(lockup.to_string(), "CPU locked at PC=0105, opcode=D3");
+
+        /* timer 继续运行，但不会响应中断 */
+        cpu.bus.write(0xFF07, 0x05);
+        let div = cpu.bus.timer.div;
+        for _ in 0..0x400 {
+            cpu.step();
+        }
+        assert_eq!(cpu.bus.timer.div, div.wrapping_add(0x1000));
+        assert_ne!(cpu.bus.interrupt.flag & InterruptKind::Timer as u8, 0);
+        assert_eq!(cpu.registers.pc, 0x106);
+    }
+
+    #[test]
+    fn interrupt_dispatch_takes_five_cycles() {
+        /* EI; NOP */
+        let mut cpu = cpu_with_program(&[0xFB, 0x00]);
+        cpu.bus.write(0xFFFF, 0x04);
+        cpu.bus.write(0xFF0F, 0x05);
+        cpu.step();
+        let div = cpu.bus.timer.div;
+        cpu.step();
+        assert_eq!(cpu.bus.timer.div.wrapping_sub(div), 6 * 4);
+        assert_eq!(cpu.registers.pc, 0x50);
+        assert_eq!(stack_top(&cpu), 0x102);
+        assert_eq!(cpu.bus.interrupt.flag, 0x01);
+        assert!(!cpu.bus.interrupt.master_enabled);
+    }
+
+    #[test]
+    fn ie_push_cancels_interrupt() {
+        /* LD SP,0x0000; EI; NOP */
+        let mut cpu = cpu_with_program(&[0x31, 0x00, 0x00, 0xFB, 0x00]);
+        cpu.bus.write(0xFFFF, 0x04);
+        cpu.bus.write(0xFF0F, 0x04);
+        for _ in 0..3 {
+            cpu.step();
+        }
+        /* PC 高字节 0x01 写入 IE，timer 中断被取消 */
+        assert_eq!(cpu.bus.interrupt.enable, 0x01);
+        assert_eq!(cpu.bus.interrupt.flag, 0x04);
+        assert_eq!(cpu.registers.pc, 0x0000);
+        assert_eq!(cpu.registers.sp, 0xFFFE);
+    }
+
+    #[test]
+    fn instruction_cycles_match_table() {
+        for flags in [0x00, 0xF0] {
+            for opcode in 0..=0xFF {
+                if let Instruction::None | Instruction::PREFIX = Instruction::from(opcode) {
+                    continue;
+                }
+                let mut cpu = cpu_with_program(&[opcode, 0x00, 0x00]);
+                cpu.registers.f = flags;
+                let taken = match Instruction::from(opcode) {
+                    Instruction::JR(condition)
+                    | Instruction::JP(condition)
+                    | Instruction::CALL(condition)
+                    | Instruction::RET(condition) => cpu.check_condition(condition),
+                    _ => true,
+                };
+                let start = cpu.bus.timer.tick;
+                cpu.step();
+                assert_eq!(
+                    (cpu.bus.timer.tick - start) / 4,
+                    Instruction::cycles(opcode, taken) as u64,
+                    "opcode {:02X} taken {}",
+                    opcode,
+                    taken
+                );
+            }
+        }
+        for opcode in 0..=0xFF {
+            let mut cpu = cpu_with_program(&[0xCB, opcode]);
+            let start = cpu.bus.timer.tick;
+            cpu.step();
+            assert_eq!(
+                (cpu.bus.timer.tick - start) / 4,
+                CBInstruction::cycles(opcode) as u64,
+                "opcode CB {:02X}",
+                opcode
+            );
+        }
+    }
+
+    #[test]
+    fn key1_follows_cgb_flag() {
+        assert_eq!(cpu_with_program(&[]).bus.read(0xFF4D), 0xFF);
+
+        let mut data = vec![0; 0x8000];
+        data[0x143] = 0x80;
+        let mut cpu = CpuContext::create(Cartridge::from(data).unwrap());
+        assert!(cpu.bus.io.cgb);
+        cpu.bus.write(0xFF4D, 0x01);
+        assert_eq!(cpu.bus.read(0xFF4D), 0x7F);
+    }
+
+    #[test]
+    fn stop_switches_speed_when_armed() {
+        /* LD A,1; LDH (0x4D),A; STOP 0 */
+        let mut cpu = cpu_with_program(&[0x3E, 0x01, 0xE0, 0x4D, 0x10, 0x00]);
+        cpu.bus.io.cgb = true;
+        for _ in 0..3 {
+            cpu.step();
+        }
+        assert!(!cpu.stopped);
+        assert_eq!(cpu.bus.read(0xFF4D), 0xFE);
+        assert_eq!(cpu.registers.pc, 0x106);
+    }
+}
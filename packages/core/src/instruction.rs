@@ -79,6 +79,15 @@ impl Instruction {
     pub fn from(opcode: u8) -> &'static Self {
         &INSTRUCTIONS[opcode as usize]
     }
+
+    /* taken 为条件跳转的条件是否成立 */
+    #[cfg(test)]
+    pub fn cycles(opcode: u8, taken: bool) -> u8 {
+        match taken {
+            true => CYCLES[opcode as usize],
+            false => NOT_TAKEN_CYCLES[opcode as usize],
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -221,6 +230,55 @@ static INSTRUCTIONS: [Instruction; 0x100] = INST_MAP![
 /* Fx */    [LD A, a8];     POPAF;          [LD A, (C)];    DI;             None;           [PUSH AF];      [OR d8];        [RST 0x30];     LDHL;           [LD SP, HL];    [LD A, a16];    EI;             None;           None;           [CP d8];        [RST 0x38];
 ];
 
+/**
+ * 每条指令占用的 M-cycle 数，条件跳转为跳转时的周期数；
+ * 0 为非法指令，0xCB 前缀指令见 CB_CYCLES
+ */
+#[cfg(test)]
+#[rustfmt::skip]
+static CYCLES: [u8; 0x100] = [
+/*        x0  x1  x2  x3  x4  x5  x6  x7  x8  x9  xA  xB  xC  xD  xE  xF  */
+/* 0x */  1,  3,  2,  2,  1,  1,  2,  1,  5,  2,  2,  2,  1,  1,  2,  1,
+/* 1x */  1,  3,  2,  2,  1,  1,  2,  1,  3,  2,  2,  2,  1,  1,  2,  1,
+/* 2x */  3,  3,  2,  2,  1,  1,  2,  1,  3,  2,  2,  2,  1,  1,  2,  1,
+/* 3x */  3,  3,  2,  2,  3,  3,  3,  1,  3,  2,  2,  2,  1,  1,  2,  1,
+/* 4x */  1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+/* 5x */  1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+/* 6x */  1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+/* 7x */  2,  2,  2,  2,  2,  2,  1,  2,  1,  1,  1,  1,  1,  1,  2,  1,
+/* 8x */  1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+/* 9x */  1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+/* Ax */  1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+/* Bx */  1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+/* Cx */  5,  3,  4,  4,  6,  4,  2,  4,  5,  4,  4,  0,  6,  6,  2,  4,
+/* Dx */  5,  3,  4,  0,  6,  4,  2,  4,  5,  4,  4,  0,  6,  0,  2,  4,
+/* Ex */  3,  3,  2,  0,  0,  4,  2,  4,  4,  1,  4,  0,  0,  0,  2,  4,
+/* Fx */  3,  3,  2,  1,  0,  4,  2,  4,  3,  2,  4,  1,  0,  0,  2,  4,
+];
+
+/* 条件不成立、不跳转时的 M-cycle 数，其他指令与 CYCLES 相同 */
+#[cfg(test)]
+#[rustfmt::skip]
+static NOT_TAKEN_CYCLES: [u8; 0x100] = [
+/*        x0  x1  x2  x3  x4  x5  x6  x7  x8  x9  xA  xB  xC  xD  xE  xF  */
+/* 0x */  1,  3,  2,  2,  1,  1,  2,  1,  5,  2,  2,  2,  1,  1,  2,  1,
+/* 1x */  1,  3,  2,  2,  1,  1,  2,  1,  3,  2,  2,  2,  1,  1,  2,  1,
+/* 2x */  2,  3,  2,  2,  1,  1,  2,  1,  2,  2,  2,  2,  1,  1,  2,  1,
+/* 3x */  2,  3,  2,  2,  3,  3,  3,  1,  2,  2,  2,  2,  1,  1,  2,  1,
+/* 4x */  1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+/* 5x */  1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+/* 6x */  1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+/* 7x */  2,  2,  2,  2,  2,  2,  1,  2,  1,  1,  1,  1,  1,  1,  2,  1,
+/* 8x */  1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+/* 9x */  1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+/* Ax */  1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+/* Bx */  1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+/* Cx */  2,  3,  3,  4,  3,  4,  2,  4,  2,  4,  3,  0,  3,  6,  2,  4,
+/* Dx */  2,  3,  3,  0,  3,  4,  2,  4,  2,  4,  3,  0,  3,  0,  2,  4,
+/* Ex */  3,  3,  2,  0,  0,  4,  2,  4,  4,  1,  4,  0,  0,  0,  2,  4,
+/* Fx */  3,  3,  2,  1,  0,  4,  2,  4,  3,  2,  4,  1,  0,  0,  2,  4,
+];
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum CBInstruction {
     None,
@@ -278,8 +336,36 @@ static CB_INSTRUCTIONS: [CBInstruction; 0x100] = CB_INST_MAP![
 /* Fx */    [SET 6,B];      [SET 6,C];      [SET 6,D];      [SET 6,E];      [SET 6,H];      [SET 6,L];      [SET 6,HL];     [SET 6,A];      [SET 7,B];      [SET 7,C];      [SET 7,D];      [SET 7,E];      [SET 7,H];      [SET 7,L];      [SET 7,HL];     [SET 7,A];
 ];
 
+/* 0xCB 前缀指令占用的 M-cycle 数，包括前缀本身 */
+#[cfg(test)]
+#[rustfmt::skip]
+static CB_CYCLES: [u8; 0x100] = [
+/*        x0  x1  x2  x3  x4  x5  x6  x7  x8  x9  xA  xB  xC  xD  xE  xF  */
+/* 0x */  2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+/* 1x */  2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+/* 2x */  2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+/* 3x */  2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+/* 4x */  2,  2,  2,  2,  2,  2,  3,  2,  2,  2,  2,  2,  2,  2,  3,  2,
+/* 5x */  2,  2,  2,  2,  2,  2,  3,  2,  2,  2,  2,  2,  2,  2,  3,  2,
+/* 6x */  2,  2,  2,  2,  2,  2,  3,  2,  2,  2,  2,  2,  2,  2,  3,  2,
+/* 7x */  2,  2,  2,  2,  2,  2,  3,  2,  2,  2,  2,  2,  2,  2,  3,  2,
+/* 8x */  2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+/* 9x */  2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+/* Ax */  2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+/* Bx */  2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+/* Cx */  2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+/* Dx */  2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+/* Ex */  2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+/* Fx */  2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+];
+
 impl CBInstruction {
     pub fn from(opcode: u8) -> &'static Self {
         &CB_INSTRUCTIONS[opcode as usize]
     }
+
+    #[cfg(test)]
+    pub fn cycles(opcode: u8) -> u8 {
+        CB_CYCLES[opcode as usize]
+    }
 }
\ No newline at end of file
@@ -23,6 +23,11 @@ impl InterruptContext {
         }
     }
 
+    /* 有已开启且正在请求的中断，与 IME 无关 */
+    pub fn is_pending(&self) -> bool {
+        self.flag & self.enable & 0x1F != 0
+    }
+
     pub fn handle_interrupts(&mut self) -> Option<u16> {
         let flag = self.flag & self.enable;
         if flag == 0 {
@@ -1,5 +1,7 @@
 use std::{cell::RefCell, rc::Rc};
 
+use crate::utils::bit;
+
 #[derive(Debug)]
 pub struct Serial {
     pub data: u8,
@@ -15,18 +17,92 @@ impl Serial {
     }
 }
 
+/**
+ * P1 (0xFF00)，bit 4 / 5 为 0 时分别选中方向键 / 功能键，低 4 位为 0 表示按下。
+ * source 返回当前按下的按键：低 4 位为 右 左 上 下，高 4 位为 A B Select Start
+ */
+pub struct Joypad {
+    select: u8,
+    source: Option<Box<dyn Fn() -> u8>>,
+}
+
+impl Joypad {
+    pub fn create() -> Self {
+        Joypad {
+            select: 0x30,
+            source: None,
+        }
+    }
+
+    pub fn set_source(&mut self, source: Box<dyn Fn() -> u8>) {
+        self.source = Some(source);
+    }
+
+    /* 被选中的按键线，低 4 位为 0 表示按下 */
+    pub fn lines(&self) -> u8 {
+        let pressed = self.source.as_ref().map_or(0, |source| source());
+        let mut lines = 0x0F;
+        if !bit!(self.select, 4) {
+            lines &= !(pressed & 0x0F);
+        }
+        if !bit!(self.select, 5) {
+            lines &= !(pressed >> 4);
+        }
+        lines
+    }
+
+    pub fn read(&self) -> u8 {
+        0xC0 | self.select | self.lines()
+    }
+
+    pub fn write(&mut self, value: u8) {
+        self.select = value & 0x30;
+    }
+}
+
+impl std::fmt::Debug for Joypad {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Joypad")
+            .field("select", &self.select)
+            .field("lines", &self.lines())
+            .finish()
+    }
+}
+
+/**
+ * CGB 的 KEY1 (0xFF4D)，bit 0 为 1 时下一条 STOP 切换双倍速
+ */
+#[derive(Debug)]
+pub struct Speed {
+    pub armed: bool,
+    pub double: bool,
+}
+
 #[derive(Debug)]
 pub struct IO {
     pub serial: Option<Rc<RefCell<Serial>>>,
+    pub joypad: Joypad,
+    pub speed: Speed,
+    /* header 声明支持 CGB 时为 true，此时 KEY1 可用 */
+    pub cgb: bool,
 }
 
 impl IO {
     pub fn create() -> Self {
-        IO { serial: None }
+        IO {
+            serial: None,
+            joypad: Joypad::create(),
+            speed: Speed {
+                armed: false,
+                double: false,
+            },
+            cgb: false,
+        }
     }
 
     pub fn read(&self, address: u16) -> u8 {
         match address {
+            0xFF00 => self.joypad.read(),
             0xFF01 => match &self.serial {
                 Some(serial) => serial.borrow().data,
                 None => 0,
@@ -35,6 +111,9 @@ impl IO {
                 Some(serial) => serial.borrow().control,
                 None => 0,
             },
+            // KEY1 不存在于 DMG，读出 0xFF，避免测试 ROM 误以为可以切换 CGB 双倍速
+            0xFF4D if !self.cgb => 0xFF,
+            0xFF4D => 0x7E | (self.speed.double as u8) << 7 | self.speed.armed as u8,
             _ => {
                 // println!("Unsupported IO read at {:X?}", address);
                 0
@@ -44,6 +123,7 @@ impl IO {
 
     pub fn write(&mut self, address: u16, value: u8) {
         match address {
+            0xFF00 => self.joypad.write(value),
             0xFF01 => match &self.serial {
                 Some(serial) => serial.borrow_mut().data = value,
                 None => {},
@@ -52,6 +132,7 @@ impl IO {
                 Some(serial) => serial.borrow_mut().control = value,
                 None => {},
             },
+            0xFF4D if self.cgb => self.speed.armed = bit!(value, 0),
             _ => {} // _ => println!("Unsupported IO write at {:X?} = {:X?}", address, value),
         }
     }
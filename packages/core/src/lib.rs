@@ -9,9 +9,11 @@ mod ram;
 mod timer;
 mod utils;
 
-use cartridge::Cartridge;
+use cartridge::{
+    entry_names, extract, header::CgbSupport, Cartridge, Dat, DumpStatus, Infrared, MapperKind,
+};
 use cpu::CpuContext;
-use js_sys::{SharedArrayBuffer, Uint8Array};
+use js_sys::{Float32Array, Function, SharedArrayBuffer, Uint8Array};
 use ppu::ScreenWriter;
 use wasm_bindgen::prelude::*;
 
@@ -31,11 +33,51 @@ extern "C" {
     fn alert(s: &str);
 }
 
+/**
+ * 卡带 header 中的信息，rom_size / ram_size 单位为字节
+ */
+#[wasm_bindgen(getter_with_clone)]
+pub struct RomInfo {
+    pub title: String,
+    pub cart_type: String,
+    pub licensee: String,
+    pub rom_size: usize,
+    pub rom_banks: usize,
+    pub ram_size: usize,
+    pub ram_banks: usize,
+    pub cgb: bool,
+    pub cgb_only: bool,
+    pub sgb: bool,
+    pub destination: String,
+    pub version: u8,
+    pub checksum_ok: bool,
+    pub global_checksum_ok: bool,
+    pub logo_ok: bool,
+    pub crc32: String,
+    pub sha1: String,
+    /* 以下为 load_dat 之后在 DAT 中找到的信息 */
+    pub dat_name: Option<String>,
+    pub region: Option<String>,
+    pub bad_dump: bool,
+}
+
+/**
+ * zip 中所有文件的名字，用于在多个 ROM 中选择一个传给 Emu 的 entry；不是 zip 时返回空数组
+ */
+#[wasm_bindgen]
+pub fn archive_entries(data: &[u8]) -> Result<Vec<String>, JsError> {
+    Ok(entry_names(data)?)
+}
+
 #[wasm_bindgen]
 pub struct Emu {
     cpu: CpuContext,
+    save_callback: Option<Function>,
 }
 
+/* run() 期间每执行这么多条指令检查一次存档是否需要写回 */
+const SAVE_INTERVAL: usize = 1 << 20;
+
 struct SharedArrayBufferWriter {
     buffer: Uint8Array,
 }
@@ -54,15 +96,92 @@ impl ScreenWriter for SharedArrayBufferWriter {
     }
 }
 
+/**
+ * 每个端口占 buffer 中的一个字节，写入本机 LED 状态，读取另一个端口作为接收信号。
+ * 两个模拟器实例分别使用端口 0 和 1 共享同一个 buffer 即可互相通信
+ */
+struct SharedArrayBufferInfrared {
+    buffer: Uint8Array,
+    port: u32,
+}
+
+impl Infrared for SharedArrayBufferInfrared {
+    fn set_led(&mut self, on: bool) {
+        self.buffer.set_index(self.port, on as u8);
+    }
+
+    fn is_receiving(&self) -> bool {
+        self.buffer.get_index(self.port ^ 1) != 0
+    }
+}
+
 #[wasm_bindgen]
 impl Emu {
+    /**
+     * cart_data 可以是 zip / gzip，entry 为 zip 中要加载的文件名，默认为第一个 .gb / .gbc；
+     * patch 为可选的 IPS / UPS / BPS 文件内容，加载时在内存中应用；
+     * mapper 为自动识别出错时手动指定的 mapper 名字，如 "mbc1"、"wisdom-tree"
+     */
     #[wasm_bindgen(constructor)]
-    pub fn create(cart_data: &mut [u8]) -> Self {
+    pub fn create(
+        cart_data: &mut [u8],
+        patch: Option<Vec<u8>>,
+        entry: Option<String>,
+        mapper: Option<String>,
+    ) -> Result<Emu, JsError> {
         set_panic_hook();
-        let cartridge = Cartridge::from(Vec::from(cart_data));
+        let data = extract(Vec::from(cart_data), entry.as_deref())?;
+        let mapper = mapper.map(|name| name.parse::<MapperKind>()).transpose()?;
+        let cartridge = Cartridge::load(data, patch.as_deref(), mapper)?;
         let cpu = CpuContext::create(cartridge);
 
-        Emu { cpu }
+        Ok(Emu {
+            cpu,
+            save_callback: None,
+        })
+    }
+
+    #[wasm_bindgen]
+    pub fn rom_info(&self) -> RomInfo {
+        let cartridge = &self.cpu.bus.cartridge;
+        let header = cartridge.header();
+        let dat_entry = cartridge.dat_entry();
+        RomInfo {
+            title: header.title_str(),
+            cart_type: header.cart_type_name().into(),
+            licensee: header.licensee_name().into(),
+            rom_size: header.rom_size_bytes(),
+            rom_banks: header.rom_banks(),
+            ram_size: header.ram_size_bytes(),
+            ram_banks: header.ram_banks(),
+            cgb: header.is_cgb(),
+            cgb_only: header.cgb_support() == CgbSupport::Only,
+            sgb: header.is_sgb(),
+            destination: if header.is_japanese() {
+                "Japanese"
+            } else {
+                "Overseas"
+            }
+            .into(),
+            version: header.version(),
+            checksum_ok: header.is_checksum_match(),
+            global_checksum_ok: header.is_global_checksum_match(),
+            logo_ok: header.is_logo_match(),
+            crc32: format!("{:08x}", cartridge.crc32()),
+            sha1: cartridge.sha1_hex(),
+            dat_name: dat_entry.map(|entry| entry.name.clone()),
+            region: dat_entry.and_then(|entry| entry.region.clone()),
+            bad_dump: dat_entry.is_some_and(|entry| entry.status == DumpStatus::BadDump),
+        }
+    }
+
+    /**
+     * 用 No-Intro / Logiqx XML DAT 识别当前 ROM，返回是否找到，结果通过 rom_info 获取
+     */
+    #[wasm_bindgen]
+    pub fn load_dat(&mut self, xml: &str) -> Result<bool, JsError> {
+        let dat = Dat::parse(xml)?;
+        Ok(self.cpu.bus.cartridge.identify(&dat).is_some())
     }
 
     #[wasm_bindgen]
@@ -81,11 +200,172 @@ impl Emu {
             .set_debug_screen_writer(Box::new(SharedArrayBufferWriter::create(buffer)));
     }
 
+    /**
+     * rumble 卡带的马达状态变化时调用 callback(on: boolean)
+     */
     #[wasm_bindgen]
-    pub fn run(&mut self) {
-        let cpu = &mut self.cpu;
+    pub fn set_rumble_callback(&mut self, callback: Function) {
+        self.cpu
+            .bus
+            .cartridge
+            .set_rumble_callback(Box::new(move |on| {
+                let _ = callback.call1(&JsValue::NULL, &JsValue::from_bool(on));
+            }));
+    }
 
-        cpu.init();
-        while cpu.step() {}
+    #[wasm_bindgen]
+    pub fn is_rumbling(&self) -> bool {
+        self.cpu.bus.cartridge.is_rumbling()
+    }
+
+    /**
+     * MBC7 加速度计的倾斜值。run() 会一直占用 worker，运行期间通过共享的
+     * Float32Array [x, y] 读取，单位为 g，水平放置时为 (0, 0)
+     */
+    #[wasm_bindgen]
+    pub fn attach_tilt_buffer(&mut self, buffer: SharedArrayBuffer) {
+        let buffer = Float32Array::new(&buffer);
+        self.cpu
+            .bus
+            .cartridge
+            .set_tilt_source(Box::new(move || (buffer.get_index(0), buffer.get_index(1))));
+    }
+
+    /**
+     * 运行期间通过共享的 Uint8Array [pressed] 读取按键，
+     * 低 4 位为 右 左 上 下，高 4 位为 A B Select Start，按下为 1
+     */
+    #[wasm_bindgen]
+    pub fn attach_joypad_buffer(&mut self, buffer: SharedArrayBuffer) {
+        let buffer = Uint8Array::new(&buffer);
+        self.cpu
+            .bus
+            .io
+            .joypad
+            .set_source(Box::new(move || buffer.get_index(0)));
+    }
+
+    #[wasm_bindgen]
+    pub fn attach_infrared_buffer(&mut self, buffer: SharedArrayBuffer, port: u32) {
+        self.cpu
+            .bus
+            .cartridge
+            .set_infrared(Box::new(SharedArrayBufferInfrared {
+                buffer: Uint8Array::new(&buffer),
+                port: port & 1,
+            }));
+    }
+
+    /**
+     * HuC3 蜂鸣器状态变化时调用 callback(tone: number | undefined)
+     */
+    #[wasm_bindgen]
+    pub fn set_tone_callback(&mut self, callback: Function) {
+        self.cpu
+            .bus
+            .cartridge
+            .set_tone_callback(Box::new(move |tone| {
+                let tone = match tone {
+                    Some(tone) => JsValue::from(tone),
+                    None => JsValue::UNDEFINED,
+                };
+                let _ = callback.call1(&JsValue::NULL, &tone);
+            }));
+    }
+
+    /**
+     * 设置 Pocket Camera 的画面，data 为 width x height 的灰度或 RGBA 像素
+     */
+    #[wasm_bindgen]
+    pub fn set_camera_image(&mut self, data: &[u8], width: usize, height: usize) -> bool {
+        self.cpu
+            .bus
+            .cartridge
+            .set_camera_image(data, width, height)
+    }
+
+    /**
+     * 运行期间每次拍摄时从共享的 RGBA buffer（如摄像头画面）读取 width x height 的图像
+     */
+    #[wasm_bindgen]
+    pub fn attach_camera_buffer(&mut self, buffer: SharedArrayBuffer, width: usize, height: usize) {
+        let buffer = Uint8Array::new(&buffer);
+        self.cpu
+            .bus
+            .cartridge
+            .set_camera_source(Box::new(move || Some((buffer.to_vec(), width, height))));
+    }
+
+    /**
+     * 最近一次拍摄的 128x112 图像，每个像素为 0-3 的颜色编号，不是 Pocket Camera 时返回 undefined
+     */
+    #[wasm_bindgen]
+    pub fn camera_capture(&self) -> Option<Vec<u8>> {
+        self.cpu.bus.cartridge.camera_capture()
+    }
+
+    /**
+     * 电池供电的外部 RAM，与其他模拟器的 .sav 格式相同，没有电池的卡带返回 undefined。
+     * 导出后清除 dirty 标记
+     */
+    #[wasm_bindgen]
+    pub fn export_save_data(&mut self) -> Option<Vec<u8>> {
+        self.cpu.bus.cartridge.export_save_data()
+    }
+
+    #[wasm_bindgen]
+    pub fn import_save_data(&mut self, data: &[u8]) {
+        self.cpu.bus.cartridge.load_save_data(data);
+    }
+
+    /**
+     * 上次导出之后存档是否被修改过
+     */
+    #[wasm_bindgen]
+    pub fn is_save_dirty(&self) -> bool {
+        self.cpu.bus.cartridge.is_save_dirty()
+    }
+
+    /**
+     * run() 会一直占用 worker，运行期间存档被修改时调用 callback(data: Uint8Array)
+     */
+    #[wasm_bindgen]
+    pub fn set_save_callback(&mut self, callback: Function) {
+        self.save_callback = Some(callback);
+    }
+
+    /**
+     * 执行非法指令导致 CPU 卡死时调用 callback(message: string)，
+     * 如 "CPU locked at PC=0105, opcode=D3"
+     */
+    #[wasm_bindgen]
+    pub fn set_lock_callback(&mut self, callback: Function) {
+        self.cpu.set_lock_callback(Box::new(move |lockup| {
+            let _ = callback.call1(&JsValue::NULL, &JsValue::from(lockup.to_string()));
+        }));
+    }
+
+    fn flush_save(&mut self) {
+        if !self.cpu.bus.cartridge.is_save_dirty() {
+            return;
+        }
+        if let Some(callback) = &self.save_callback {
+            if let Some(data) = self.cpu.bus.cartridge.export_save_data() {
+                let _ = callback.call1(&JsValue::NULL, &Uint8Array::from(&data[..]));
+            }
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn run(&mut self) {
+        self.cpu.init();
+        let mut steps: usize = 0;
+        while self.cpu.step() {
+            steps = steps.wrapping_add(1);
+            if steps.is_multiple_of(SAVE_INTERVAL) {
+                self.flush_save();
+            }
+        }
+        self.flush_save();
     }
 }
@@ -9,26 +9,77 @@ mod ram;
 mod timer;
 mod utils;
 
-use cartridge::Cartridge;
+use cartridge::{entry_names, extract, Cartridge, Dat, DumpStatus, MapperKind};
 use cpu::CpuContext;
 use emu::Emu;
 use io::Serial;
-use std::{cell::RefCell, env, io::Read, rc::Rc};
+use std::{
+    cell::RefCell,
+    env,
+    io::Read,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
-pub fn load_cartridge(filename: &String) -> std::io::Result<Cartridge> {
+/* 每执行这么多条指令检查一次存档是否需要写回 */
+const SAVE_INTERVAL: usize = 1 << 20;
+
+fn invalid_data<E: std::error::Error + Send + Sync + 'static>(err: E) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+}
+
+/**
+ * filename 可以是 zip / gzip，entry 为 zip 中要加载的文件名，默认为第一个 .gb / .gbc；
+ * mapper 为手动指定的 mapper 名字
+ */
+pub fn load_cartridge(
+    filename: &String,
+    entry: Option<&str>,
+    patch: Option<&String>,
+    mapper: Option<&str>,
+) -> std::io::Result<Cartridge> {
     let mut file = std::fs::File::open(filename)?;
     let mut data = Vec::new();
     file.read_to_end(&mut data)?;
-    Ok(Cartridge { data })
+    let data = extract(data, entry).map_err(invalid_data)?;
+    let patch = patch.map(std::fs::read).transpose()?;
+    let mapper = mapper
+        .map(|name| name.parse::<MapperKind>())
+        .transpose()
+        .map_err(invalid_data)?;
+    Cartridge::load(data, patch.as_deref(), mapper).map_err(invalid_data)
+}
+
+/**
+ * 与 ROM 同目录同名的 .sav 文件，格式与其他模拟器通用：
+ * 外部 RAM 原样导出，带 RTC 的卡带在末尾附加 footer
+ */
+fn save_path(filename: &str) -> PathBuf {
+    Path::new(filename).with_extension("sav")
+}
+
+fn write_save(cartridge: &mut Cartridge, path: &Path) -> std::io::Result<()> {
+    if let Some(data) = cartridge.export_save_data() {
+        std::fs::write(path, data)?;
+    }
+    Ok(())
 }
 
 impl Emu {
     pub fn run_test(filename: String, limit: usize) -> std::io::Result<String> {
-        let mut cartridge = load_cartridge(&filename)?;
+        Self::run_cartridge(load_cartridge(&filename, None, None, None)?, limit, None)
+    }
+
+    fn run_cartridge(
+        cartridge: Cartridge,
+        limit: usize,
+        save_path: Option<&Path>,
+    ) -> std::io::Result<String> {
         let serial = Rc::new(RefCell::new(Serial::create()));
 
         let mut cpu = CpuContext::create(cartridge);
         cpu.bus.io.serial = Some(Rc::clone(&serial));
+        cpu.set_lock_callback(Box::new(|lockup| println!("{}", lockup)));
 
         cpu.init();
 
@@ -45,15 +96,81 @@ impl Emu {
                 serial.control = 0;
                 println!("{}", dbg_msg);
             }
-            cycles += 1
+            cycles += 1;
+            if let Some(path) = save_path {
+                if cycles.is_multiple_of(SAVE_INTERVAL) && cpu.bus.cartridge.is_save_dirty() {
+                    write_save(&mut cpu.bus.cartridge, path)?;
+                }
+            }
+        }
+        if let Some(path) = save_path {
+            if cpu.bus.cartridge.is_save_dirty() {
+                write_save(&mut cpu.bus.cartridge, path)?;
+            }
+        }
+        /* 较新的 Blargg 测试 ROM 不使用串口，0xA001 开始为签名 DE B0 61，结果文本从 0xA004 开始 */
+        if dbg_msg.is_empty()
+            && (0xA001..=0xA003)
+                .map(|a| cpu.bus.read(a))
+                .eq([0xDE, 0xB0, 0x61])
+        {
+            dbg_msg = (0xA004..0xC000)
+                .map(|a| cpu.bus.read(a))
+                .take_while(|&c| c != 0)
+                .map(|c| c as char)
+                .collect();
         }
         Ok(dbg_msg)
     }
 }
 
 fn main() -> std::io::Result<()> {
-    if let Some(filename) = env::args().nth(1) {
-        Emu::run_test(filename, usize::MAX)?;
+    /*
+     * --entry=<name> 指定 zip 中要加载的文件，--list 列出 zip 中的所有文件，
+     * --dat=<file> 用 No-Intro DAT 识别 ROM，--mapper=<name> 手动指定 mapper
+     */
+    let (options, args): (Vec<String>, Vec<String>) =
+        env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    let option = |name: &str| {
+        options
+            .iter()
+            .find_map(|option| option.strip_prefix(name)?.strip_prefix('='))
+    };
+    let entry = option("--entry");
+    if let Some(filename) = args.first() {
+        if options.iter().any(|option| option == "--list") {
+            for name in entry_names(&std::fs::read(filename)?).map_err(invalid_data)? {
+                println!("{}", name);
+            }
+            return Ok(());
+        }
+        /* 第二个参数为可选的 IPS / UPS / BPS patch */
+        let mut cartridge = load_cartridge(filename, entry, args.get(1), option("--mapper"))?;
+        println!(
+            "CRC32: {:08x}  SHA-1: {}",
+            cartridge.crc32(),
+            cartridge.sha1_hex()
+        );
+        if let Some(dat) = option("--dat") {
+            let dat = Dat::parse(&std::fs::read_to_string(dat)?).map_err(invalid_data)?;
+            match cartridge.identify(&dat) {
+                Some(entry) => println!(
+                    "{} [{}]{}",
+                    entry.name,
+                    entry.region.as_deref().unwrap_or("Unknown"),
+                    match entry.status {
+                        DumpStatus::BadDump => " (bad dump)",
+                        _ => "",
+                    }
+                ),
+                None => println!("ROM not found in DAT"),
+            }
+        }
+        let save_path = save_path(filename);
+        if cartridge.has_battery() && save_path.exists() {
+            cartridge.load_save_data(&std::fs::read(&save_path)?);
+        }
+        Emu::run_cartridge(cartridge, usize::MAX, Some(&save_path))?;
         Ok(())
     } else {
         panic!("must pass filename")
@@ -64,6 +181,12 @@ fn main() -> std::io::Result<()> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn save_next_to_rom() {
+        assert_eq!(save_path("./roms/game.gb"), PathBuf::from("./roms/game.sav"));
+        assert_eq!(save_path("game.gbc"), PathBuf::from("game.sav"));
+    }
+
     #[test]
     fn rom_test01() -> std::io::Result<()> {
         assert_eq!(
@@ -163,12 +286,100 @@ mod tests {
         Ok(())
     }
 
-    // #[test]
-    // fn cpu_instrs() -> std::io::Result<()> {
-    //     assert_eq!(
-    //         Emu::run_test("./roms/cpu_instrs.gb".into(), 10000000)?,
-    //         "cpu_instrs\n\n\nPassed\n",
-    //     );
-    //     Ok(())
-    // }
+    /* mooneye 测试通过时通过串口输出斐波那契数列 3 5 8 13 21 34 */
+    fn run_mooneye(filename: &str, limit: usize) -> std::io::Result<()> {
+        let output = Emu::run_test(filename.into(), limit)?;
+        assert!(output.ends_with("\x03\x05\x08\x0D\x15\x22"), "{:?}", output);
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "requires roms/halt_bug.gb"]
+    fn halt_bug() -> std::io::Result<()> {
+        assert_eq!(
+            Emu::run_test("./roms/halt_bug.gb".into(), 5000000)?,
+            "halt bug\n\n\nPassed\n",
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "requires roms/halt_ime0_ei.gb"]
+    fn halt_ime0_ei() -> std::io::Result<()> {
+        run_mooneye("./roms/halt_ime0_ei.gb", 1000000)
+    }
+
+    #[test]
+    #[ignore = "requires roms/halt_ime0_nointr_timing.gb"]
+    fn halt_ime0_nointr_timing() -> std::io::Result<()> {
+        run_mooneye("./roms/halt_ime0_nointr_timing.gb", 1000000)
+    }
+
+    #[test]
+    #[ignore = "requires roms/halt_ime1_timing.gb"]
+    fn halt_ime1_timing() -> std::io::Result<()> {
+        run_mooneye("./roms/halt_ime1_timing.gb", 1000000)
+    }
+
+    #[test]
+    #[ignore = "requires roms/halt_ime1_timing2-GS.gb"]
+    fn halt_ime1_timing2() -> std::io::Result<()> {
+        run_mooneye("./roms/halt_ime1_timing2-GS.gb", 1000000)
+    }
+
+    #[test]
+    #[ignore = "requires roms/ie_push.gb"]
+    fn ie_push() -> std::io::Result<()> {
+        run_mooneye("./roms/ie_push.gb", 1000000)
+    }
+
+    #[test]
+    #[ignore = "requires roms/intr_timing.gb"]
+    fn intr_timing() -> std::io::Result<()> {
+        run_mooneye("./roms/intr_timing.gb", 1000000)
+    }
+
+    #[test]
+    #[ignore = "requires roms/di_timing-GS.gb"]
+    fn di_timing() -> std::io::Result<()> {
+        run_mooneye("./roms/di_timing-GS.gb", 1000000)
+    }
+
+    #[test]
+    fn mem_timing() -> std::io::Result<()> {
+        assert_eq!(
+            Emu::run_test("./roms/mem_timing.gb".into(), 5000000)?,
+            "mem_timing\n\n01:ok  02:ok  03:ok  \n\nPassed all tests\n",
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "requires roms/mem_timing-2.gb"]
+    fn mem_timing_2() -> std::io::Result<()> {
+        assert_eq!(
+            Emu::run_test("./roms/mem_timing-2.gb".into(), 5000000)?,
+            "mem_timing\n\n01:ok  02:ok  03:ok  \n\nPassed all tests\n",
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "requires roms/instr_timing.gb"]
+    fn instr_timing() -> std::io::Result<()> {
+        assert_eq!(
+            Emu::run_test("./roms/instr_timing.gb".into(), 5000000)?,
+            "instr_timing\n\n\nPassed\n",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn cpu_instrs() -> std::io::Result<()> {
+        assert_eq!(
+            Emu::run_test("./roms/cpu_instrs.gb".into(), 30000000)?,
+            "cpu_instrs\n\n01:ok  02:ok  03:ok  04:ok  05:ok  06:ok  07:ok  08:ok  09:ok  10:ok  11:ok  \n\nPassed all tests\n",
+        );
+        Ok(())
+    }
 }
@@ -314,6 +314,18 @@ impl PPU {
         }
     }
 
+    /* STOP 期间 LCD 不显示，整个屏幕为白色 */
+    pub fn blank(&mut self) {
+        if let Some(screen_writer) = &mut self.screen_writer {
+            for idx in 0..X_RES * Y_RES {
+                screen_writer.set_index(idx * 4, EMPTY_COLOR.r);
+                screen_writer.set_index(idx * 4 + 1, EMPTY_COLOR.g);
+                screen_writer.set_index(idx * 4 + 2, EMPTY_COLOR.b);
+                screen_writer.set_index(idx * 4 + 3, EMPTY_COLOR.a);
+            }
+        }
+    }
+
     fn pipeline_reset(&mut self) {
         self.pfc.pixel_fifo.clear();
     }
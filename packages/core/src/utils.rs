@@ -19,9 +19,9 @@ pub(super) use bit;
 macro_rules! set_bit {
     ($exp:expr, $n:literal, $b:expr) => {{
         if $b {
-            $exp = ($exp | (1 << $n))
+            $exp |= 1 << $n
         } else {
-            $exp = ($exp & !(1 << $n))
+            $exp &= !(1 << $n)
         }
     }}
 }
@@ -76,3 +76,27 @@ impl<T: Clone> RingBuffer<T> {
         item
     }
 }
+
+/**
+ * CRC-32 (IEEE 802.3)，与 zip / UPS / BPS 使用的相同
+ */
+pub fn crc32(data: &[u8]) -> u32 {
+    static TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 != 0 { 0xEDB88320 ^ (crc >> 1) } else { crc >> 1 };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+    !data.iter().fold(!0u32, |crc, &byte| {
+        TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
@@ -1,4 +1,5 @@
 import { render } from "solid-js/web";
+import type { WorkerMessage } from "./worker";
 
 const Y_RES = 144;
 const X_RES = 160;
@@ -39,39 +40,146 @@ const initCanvas = (canvas: HTMLCanvasElement, width: number, height: number) =>
   return buffer;
 };
 
+// MBC7 倾斜传感器输入，[x, y] 单位为 g
+const initTilt = () => {
+  const buffer = new SharedArrayBuffer(2 * Float32Array.BYTES_PER_ELEMENT);
+  const tilt = new Float32Array(buffer);
+  const toG = (degree: number | null) => Math.sin(((degree ?? 0) * Math.PI) / 180);
+
+  window.addEventListener("deviceorientation", (e) => {
+    tilt[0] = toG(e.gamma);
+    tilt[1] = toG(e.beta);
+  });
+
+  return buffer;
+};
+
+// 按键输入，低 4 位为 右 左 上 下，高 4 位为 A B Select Start
+const JOYPAD_KEYS: Record<string, number> = {
+  ArrowRight: 1 << 0,
+  ArrowLeft: 1 << 1,
+  ArrowUp: 1 << 2,
+  ArrowDown: 1 << 3,
+  KeyZ: 1 << 4,
+  KeyX: 1 << 5,
+  ShiftRight: 1 << 6,
+  Enter: 1 << 7,
+};
+
+const initJoypad = () => {
+  const buffer = new SharedArrayBuffer(1);
+  const pressed = new Uint8Array(buffer);
+
+  window.addEventListener("keydown", (e) => {
+    if (e.code in JOYPAD_KEYS) pressed[0] |= JOYPAD_KEYS[e.code];
+  });
+  window.addEventListener("keyup", (e) => {
+    if (e.code in JOYPAD_KEYS) pressed[0] &= ~JOYPAD_KEYS[e.code];
+  });
+
+  return buffer;
+};
+
+// 电池存档保存在 IndexedDB 中，以 ROM 文件名为 key
+const SAVE_STORE = "saves";
+
+const openSaveDB = () =>
+  new Promise<IDBDatabase>((resolve, reject) => {
+    const request = indexedDB.open("gbemu", 1);
+    request.onupgradeneeded = () => request.result.createObjectStore(SAVE_STORE);
+    request.onsuccess = () => resolve(request.result);
+    request.onerror = () => reject(request.error);
+  });
+
+const loadSave = async (key: string) => {
+  const db = await openSaveDB();
+  return new Promise<Uint8Array | undefined>((resolve, reject) => {
+    const request = db.transaction(SAVE_STORE).objectStore(SAVE_STORE).get(key);
+    request.onsuccess = () => resolve(request.result);
+    request.onerror = () => reject(request.error);
+  });
+};
+
+const storeSave = async (key: string, data: Uint8Array) => {
+  const db = await openSaveDB();
+  db.transaction(SAVE_STORE, "readwrite").objectStore(SAVE_STORE).put(data, key);
+};
+
+// 输入监听只注册一次，之后加载的 ROM 共用同一块 buffer
+const tiltBuffer = initTilt();
+const joypadBuffer = initJoypad();
+
+const readFile = async (file: File) => {
+  const reader = await new Promise<FileReader>((resolve, reject) => {
+    const reader = new FileReader();
+    reader.onload = () => {
+      resolve(reader);
+    };
+    reader.onerror = (ev) => {
+      console.error("read file error", ev);
+      reject(ev);
+    };
+    reader.readAsArrayBuffer(file);
+  });
+  return reader.result as ArrayBuffer;
+};
+
 const Emu = () => {
+  // 可选的 IPS / UPS / BPS patch，需要在选择 ROM 之前选择
+  let patchFile: File | undefined;
+  // zip 中包含多个 ROM 时要加载的文件名
+  let entry: string | undefined;
+  let datFile: File | undefined;
+  let mapper: string | undefined;
+
   const handleFileSelection = async (
     event: Event & { target: HTMLInputElement }
   ) => {
     const file = event.target.files?.[0];
     if (!file) return;
-    const reader = await new Promise<FileReader>((resolve, reject) => {
-      const reader = new FileReader();
-      reader.onload = () => {
-        resolve(reader);
-      };
-      reader.onerror = (ev) => {
-        console.error("read file error", ev);
-        reject(ev);
-      };
-      reader.readAsArrayBuffer(file);
-    });
-    const arrayBuffer = reader.result as ArrayBuffer;
+    const arrayBuffer = await readFile(file);
+    const patchData = patchFile && (await readFile(patchFile));
+    const datText = await datFile?.text();
 
     const mainBuffer = initCanvas(mainScreenCanvas!, X_RES, Y_RES)
     const debugBuffer = initCanvas(debugScreenCanvas!, DEBUG_X_RES, DEBUG_Y_RES)
+    const saveData = await loadSave(file.name).catch(() => undefined);
 
     worker.postMessage(
       {
         cartData: arrayBuffer,
         mainBuffer: mainBuffer,
         debugBuffer: debugBuffer,
+        tiltBuffer: tiltBuffer,
+        joypadBuffer: joypadBuffer,
+        saveData: saveData,
+        patchData: patchData,
+        entry: entry,
+        datText: datText,
+        mapper: mapper,
       },
-      [arrayBuffer]
+      patchData ? [arrayBuffer, patchData] : [arrayBuffer]
     );
 
-    worker.onmessage = (e) => {
-      console.log(e);
+    worker.onmessage = (e: MessageEvent<WorkerMessage>) => {
+      switch (e.data.type) {
+        case "rumble":
+          // 马达开启时持续振动，直到下一次关闭
+          navigator.vibrate?.(e.data.on ? 1000 : 0);
+          break;
+        case "error":
+          alert(e.data.message);
+          break;
+        case "locked":
+          // 非法指令导致 CPU 卡死，PPU 仍在运行但游戏不再响应
+          alert(e.data.message);
+          break;
+        case "save":
+          storeSave(file.name, e.data.data).catch(console.error);
+          break;
+        default:
+          console.log(e);
+      }
     };
   };
 
@@ -89,6 +197,30 @@ const Emu = () => {
         ref={debugScreenCanvas}
       />
       <input type="file" onChange={handleFileSelection}></input>
+      <label>
+        patch
+        <input
+          type="file"
+          accept=".ips,.ups,.bps"
+          onChange={(e) => (patchFile = e.target.files?.[0])}
+        ></input>
+      </label>
+      <label>
+        zip entry
+        <input type="text" onChange={(e) => (entry = e.target.value || undefined)}></input>
+      </label>
+      <label>
+        DAT
+        <input
+          type="file"
+          accept=".dat,.xml"
+          onChange={(e) => (datFile = e.target.files?.[0])}
+        ></input>
+      </label>
+      <label>
+        mapper
+        <input type="text" onChange={(e) => (mapper = e.target.value || undefined)}></input>
+      </label>
     </>
   );
 };
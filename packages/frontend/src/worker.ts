@@ -4,16 +4,60 @@ export type WorkerRequest = {
   cartData: ArrayBuffer;
   mainBuffer: SharedArrayBuffer;
   debugBuffer: SharedArrayBuffer;
+  tiltBuffer: SharedArrayBuffer;
+  joypadBuffer: SharedArrayBuffer;
+  saveData?: Uint8Array;
+  patchData?: ArrayBuffer;
+  // zip 中要加载的文件名，默认为第一个 .gb / .gbc
+  entry?: string;
+  // No-Intro / Logiqx XML DAT，用于识别 ROM
+  datText?: string;
+  // 自动识别出错时手动指定的 mapper，如 "mbc1"、"wisdom-tree"
+  mapper?: string;
 };
 
+export type WorkerMessage =
+  | { type: "rumble"; on: boolean }
+  | { type: "error"; message: string }
+  | { type: "save"; data: Uint8Array }
+  | { type: "locked"; message: string };
+
+const post = (message: WorkerMessage) => self.postMessage(message);
+
 self.onmessage = (ev: MessageEvent<WorkerRequest>) => {
   console.log("onmessage", ev.data);
-  const { cartData, mainBuffer, debugBuffer } = ev.data;
+  const { cartData, mainBuffer, debugBuffer, tiltBuffer, joypadBuffer, saveData, patchData, entry, datText, mapper } = ev.data;
+
+  let emu: Emu;
+  try {
+    emu = new Emu(
+      new Uint8Array(cartData),
+      patchData && new Uint8Array(patchData),
+      entry,
+      mapper,
+    );
+  } catch (e) {
+    post({ type: "error", message: e instanceof Error ? e.message : String(e) });
+    return;
+  }
 
-  const emu = new Emu(new Uint8Array(cartData));
+  if (datText) {
+    try {
+      emu.load_dat(datText);
+    } catch (e) {
+      console.error(e);
+    }
+  }
+  console.log("rom info", emu.rom_info());
 
   emu.attach_screen_buffer(mainBuffer);
   emu.attach_debug_screen_buffer(debugBuffer);
+  emu.attach_tilt_buffer(tiltBuffer);
+  emu.attach_joypad_buffer(joypadBuffer);
+  emu.set_rumble_callback((on: boolean) => post({ type: "rumble", on }));
+  if (saveData) emu.import_save_data(saveData);
+  emu.set_save_callback((data: Uint8Array) => post({ type: "save", data }));
+  emu.set_lock_callback((message: string) => post({ type: "locked", message }));
 
   emu.run()
 };